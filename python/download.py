@@ -3,6 +3,7 @@ from os import path
 import bs4
 import threading
 import time
+from concurrent.futures import ThreadPoolExecutor
 import pandas
 import cloudscraper
 import sys
@@ -11,8 +12,14 @@ import sys
 from regions import Regions
 
 
+DATA_DIR = "/home/ubuntu/project/chartscan/SpotifyData"
+# Downloads of a plan that run at the same time
+PLAN_WORKERS = 8
+
+
+# Same root as the Rust side, moved with the CHARTSCAN_DATA environment variable
 def get_location(region):
-    return "/home/ubuntu/project/chartscan/SpotifyData/" + region
+    return os.path.join(os.environ.get("CHARTSCAN_DATA", DATA_DIR), region)
 
 
 def range_date(start_date, end_date):
@@ -144,7 +151,29 @@ def download_all_regions(date):
         thread.join()
 
 
+# Download the (code, date) pairs of a backfill plan written by `chart gaps --plan`
+def download_plan(plan_file):
+    downloads = []
+    with open(plan_file) as f, ThreadPoolExecutor(max_workers=PLAN_WORKERS) as pool:
+        for line in f:
+            line = line.strip()
+            if not line:
+                continue
+            region_code, date = line.split(",")
+            future = pool.submit(
+                get_download, date, Regions(region_code).name, region_code)
+            downloads.append((future, region_code, date))
+
+    # The pool keeps exceptions, a plain thread would have printed them
+    for future, region_code, date in downloads:
+        if future.exception() is not None:
+            print("[Error]:", region_code, date, future.exception())
+
+
 start_time = time.time()
 
 if __name__ == "__main__":
-    download_all_regions("2022-06-02")
+    if len(sys.argv) > 1:
+        download_plan(sys.argv[1])
+    else:
+        download_all_regions("2022-06-02")
@@ -1,7 +1,18 @@
 #![allow(dead_code)]
 use clap::{Parser, Subcommand};
 use num_format::{Locale, ToFormattedString};
-use spotify::{regions::RegionString, SpotifyChart, SpotifyGain};
+use spotify::{
+    anomaly::Method,
+    debut::DebutKind,
+    filter::Filter,
+    fmt::{DailyStyle, Format},
+    history::Smoothing,
+    matrix::Value,
+    project::Model,
+    records::Category,
+    svg::SvgOptions,
+    watch::{Report, WatchOptions},
+};
 
 mod spotify;
 
@@ -71,6 +82,10 @@ enum Commands {
         /// Previous date
         #[clap(short, long)]
         previous_date: Option<String>,
+
+        /// Filter expression, e.g. 'artist:"BTS" and not title:/remix/i or rank<=10'
+        #[clap(short, long = "where")]
+        where_expr: Option<String>,
     },
     /// Spotify chart
     Daily {
@@ -85,6 +100,545 @@ enum Commands {
         /// Title keyword
         #[clap(short, long)]
         artist: Option<String>,
+
+        /// Filter expression, e.g. 'artist:"BTS" and not title:/remix/i or rank<=10'
+        #[clap(short, long = "where")]
+        where_expr: Option<String>,
+
+        /// How the regions are printed
+        #[clap(short, long, value_enum, default_value = "html")]
+        style: DailyStyle,
+
+        /// Days of streams and rank sparklines, only in the text style
+        #[clap(long, default_value = "0")]
+        spark: usize,
+    },
+    /// Artist summary per region: songs on the chart, best rank and streams.
+    /// Credits are split from the chart's artist and title text as a best guess,
+    /// names holding a comma are kept whole when listed in keep-whole.txt in the
+    /// data directory
+    Artist {
+        /// Artist name
+        artist: String,
+
+        /// Date
+        #[clap(short, long)]
+        date: String,
+
+        /// Only count songs where the artist is a lead artist
+        #[clap(long)]
+        no_features: bool,
+    },
+    /// Cross-region matrix of tracks and where they chart
+    Matrix {
+        /// Date
+        #[clap(short, long)]
+        date: String,
+
+        /// Title keyword
+        #[clap(short, long)]
+        title: Option<String>,
+
+        /// Artist keyword
+        #[clap(short, long)]
+        artist: Option<String>,
+
+        /// Filter expression, e.g. 'artist:"BTS" and not title:/remix/i or rank<=10'
+        #[clap(short, long = "where")]
+        where_expr: Option<String>,
+
+        /// Value shown in the cells
+        #[clap(long, value_enum, default_value = "rank")]
+        value: Value,
+
+        /// Output format
+        #[clap(short, long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    /// Chart concentration and streams share over a date range
+    Stats {
+        /// Region code
+        #[clap(short, long)]
+        code: String,
+
+        /// First date
+        #[clap(long)]
+        from: String,
+
+        /// Last date
+        #[clap(long)]
+        to: String,
+
+        /// Title keyword, its share of the chart streams is reported
+        #[clap(short, long)]
+        title: Option<String>,
+
+        /// Artist keyword, its share of the chart streams is reported
+        #[clap(short, long)]
+        artist: Option<String>,
+
+        /// Filter expression, its share of the chart streams is reported
+        #[clap(short, long = "where")]
+        where_expr: Option<String>,
+
+        /// Output format
+        #[clap(short, long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    /// Daily rank and streams of a track over a date range
+    History {
+        /// Region code
+        #[clap(short, long)]
+        code: String,
+
+        /// First date
+        #[clap(long)]
+        from: String,
+
+        /// Last date
+        #[clap(long)]
+        to: String,
+
+        /// Title keyword
+        #[clap(short, long)]
+        title: Option<String>,
+
+        /// Artist keyword
+        #[clap(short, long)]
+        artist: Option<String>,
+
+        /// Filter expression, e.g. 'artist:"BTS" and not title:/remix/i or rank<=10'
+        #[clap(short, long = "where")]
+        where_expr: Option<String>,
+
+        /// Fill off-chart days with an estimate from the rank to streams curve
+        #[clap(short, long)]
+        estimate: bool,
+
+        /// Add the trailing 7-day average of streams, from days on the chart only
+        #[clap(long)]
+        moving_average: bool,
+
+        /// Add the change against the same weekday a week before
+        #[clap(long)]
+        week_over_week: bool,
+
+        /// Add the day over day change with the weekday effect taken out
+        #[clap(long)]
+        weekday_adjusted: bool,
+
+        /// Draw rank and streams to this SVG file
+        #[clap(long)]
+        svg: Option<String>,
+
+        /// Draw streams as bars instead of a line
+        #[clap(long)]
+        bars: bool,
+
+        /// Filter expression of another song to draw, can be given more than once
+        #[clap(long)]
+        compare: Vec<String>,
+
+        /// Draw the song in another region too, can be given more than once
+        #[clap(long)]
+        compare_code: Vec<String>,
+
+        /// Output format
+        #[clap(short, long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    /// Project when a track's cumulative streams cross milestones
+    Project {
+        /// Region code
+        #[clap(short, long)]
+        code: String,
+
+        /// First date counted in the cumulative streams
+        #[clap(long)]
+        from: String,
+
+        /// Last date, projections start the day after
+        #[clap(long)]
+        to: String,
+
+        /// Title keyword
+        #[clap(short, long)]
+        title: Option<String>,
+
+        /// Artist keyword
+        #[clap(short, long)]
+        artist: Option<String>,
+
+        /// Filter expression, e.g. 'artist:"BTS" and not title:/remix/i or rank<=10'
+        #[clap(short, long = "where")]
+        where_expr: Option<String>,
+
+        /// Count off-chart days with an estimate from the rank to streams curve
+        #[clap(short, long)]
+        estimate: bool,
+
+        /// Trend model fitted to the recent daily streams
+        #[clap(short, long, value_enum, default_value = "exponential")]
+        model: Model,
+
+        /// Number of recent days the trend is fitted on
+        #[clap(long, default_value = "14")]
+        days: usize,
+
+        /// Streams before the first date, added to the cumulative total
+        #[clap(long, default_value = "0")]
+        base: i64,
+
+        /// Milestones in streams
+        #[clap(
+            long,
+            use_value_delimiter = true,
+            default_value = "100000000,500000000,1000000000"
+        )]
+        milestones: Vec<i64>,
+
+        /// Output format
+        #[clap(short, long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    /// Streaks and records from the daily charts of a region
+    Records {
+        /// Region code
+        #[clap(short, long)]
+        code: String,
+
+        /// First date
+        #[clap(long)]
+        from: String,
+
+        /// Last date
+        #[clap(long)]
+        to: String,
+
+        /// Record categories, all of them when not given
+        #[clap(long = "category", value_enum, use_value_delimiter = true)]
+        categories: Vec<Category>,
+
+        /// Records per artist instead of per track
+        #[clap(long)]
+        artists: bool,
+
+        /// Only count songs where the artist is a lead artist
+        #[clap(long)]
+        no_features: bool,
+
+        /// Number of entries shown per category
+        #[clap(short, long, default_value = "10")]
+        limit: usize,
+
+        /// Output format
+        #[clap(short, long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    /// New entries and re-entries of a region
+    Debuts {
+        /// Region code
+        #[clap(short, long)]
+        code: String,
+
+        /// First date, the start of the archive when not given
+        #[clap(long)]
+        from: Option<String>,
+
+        /// Last date, the end of the archive when not given
+        #[clap(long)]
+        to: Option<String>,
+
+        /// Only list debuts or re-entries
+        #[clap(short, long, value_enum)]
+        kind: Option<DebutKind>,
+
+        /// Rank debuts by position instead of listing by date, re-entries only with --kind
+        #[clap(short, long)]
+        biggest: bool,
+
+        /// Count debuts per artist, re-entries only with --kind
+        #[clap(short = 'a', long)]
+        by_artist: bool,
+
+        /// Number of entries shown
+        #[clap(short, long)]
+        limit: Option<usize>,
+
+        /// Output format
+        #[clap(short, long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    /// Flag daily streams changes that are unusual for a track's history
+    Anomalies {
+        /// Region code
+        #[clap(short, long)]
+        code: String,
+
+        /// First date
+        #[clap(long)]
+        from: String,
+
+        /// Last date
+        #[clap(long)]
+        to: String,
+
+        /// Number of earlier daily changes a change is compared to
+        #[clap(long, default_value = "14")]
+        window: usize,
+
+        /// Score from which a change is flagged
+        #[clap(long, default_value = "3.5")]
+        threshold: f64,
+
+        /// Scoring method
+        #[clap(short, long, value_enum, default_value = "mad")]
+        method: Method,
+
+        /// Title keyword
+        #[clap(short, long)]
+        title: Option<String>,
+
+        /// Artist keyword
+        #[clap(short, long)]
+        artist: Option<String>,
+
+        /// Filter expression, e.g. 'artist:"BTS" and not title:/remix/i or rank<=10'
+        #[clap(short, long = "where")]
+        where_expr: Option<String>,
+
+        /// Output format
+        #[clap(short, long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    /// Chart of a month, quarter, year or custom range from summed daily streams
+    Aggregate {
+        /// Region code
+        #[clap(short, long)]
+        code: String,
+
+        /// Period: a year (2022), quarter (2022-Q2) or month (2022-05)
+        #[clap(short, long, conflicts_with_all = &["from", "to"])]
+        period: Option<String>,
+
+        /// First date
+        #[clap(long, requires = "to")]
+        from: Option<String>,
+
+        /// Last date
+        #[clap(long, requires = "from")]
+        to: Option<String>,
+
+        /// Title keyword
+        #[clap(short, long)]
+        title: Option<String>,
+
+        /// Artist keyword
+        #[clap(short, long)]
+        artist: Option<String>,
+
+        /// Filter expression, e.g. 'artist:"BTS" and not title:/remix/i or rank<=10'
+        #[clap(short, long = "where")]
+        where_expr: Option<String>,
+
+        /// Only keep the top entries
+        #[clap(short, long)]
+        limit: Option<usize>,
+
+        /// Also write the chart to this file in the archive's `*` format
+        #[clap(short, long)]
+        out: Option<String>,
+
+        /// Output format
+        #[clap(short, long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    /// Combined chart of all tracked regions against the global chart
+    Combined {
+        /// Date
+        #[clap(short, long)]
+        date: String,
+
+        /// Title keyword
+        #[clap(short, long)]
+        title: Option<String>,
+
+        /// Artist keyword
+        #[clap(short, long)]
+        artist: Option<String>,
+
+        /// Filter expression, e.g. 'artist:"BTS" and not title:/remix/i or rank<=10'
+        #[clap(short, long = "where")]
+        where_expr: Option<String>,
+
+        /// Only show the top entries
+        #[clap(short, long)]
+        limit: Option<usize>,
+
+        /// Output format
+        #[clap(short, long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    /// Check every chart file in the data directory for broken data
+    Validate {
+        /// Only check this region code
+        #[clap(short, long)]
+        code: Option<String>,
+
+        /// Rows every chart file should have
+        #[clap(long, default_value_t = spotify::integrity::EXPECTED_ROWS)]
+        rows: usize,
+
+        /// Only list the files with problems
+        #[clap(long)]
+        failed_only: bool,
+
+        /// Output format
+        #[clap(short, long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    /// List missing chart dates per region and write a backfill plan
+    Gaps {
+        /// Only check this region code
+        #[clap(short, long)]
+        code: Option<String>,
+
+        /// First date, the earliest chart found by default
+        #[clap(long)]
+        from: Option<String>,
+
+        /// Last date, the latest chart found by default
+        #[clap(long)]
+        to: Option<String>,
+
+        /// Write the missing (code, date) pairs to this file for the downloader
+        #[clap(short, long)]
+        plan: Option<String>,
+
+        /// Output format
+        #[clap(short, long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    /// Serve the chart archive as a JSON API over HTTP
+    Serve {
+        /// Address to listen on
+        #[clap(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+    },
+    /// Browse the charts in a full-screen terminal UI
+    Tui {
+        /// Region code to start with
+        #[clap(short, long)]
+        code: Option<String>,
+
+        /// Date to start with, the latest chart by default
+        #[clap(short, long)]
+        date: Option<String>,
+    },
+    /// Interactive shell that keeps loaded charts in memory
+    Shell {
+        /// Region code to start with
+        #[clap(short, long)]
+        code: Option<String>,
+
+        /// Date to start with, the latest chart by default
+        #[clap(short, long, requires = "code")]
+        date: Option<String>,
+    },
+    /// Watch the data directory and rerun reports for new chart files
+    Watch {
+        /// Directory the reports are written to
+        #[clap(short, long)]
+        out: String,
+
+        /// Reports to run for each new file
+        #[clap(
+            long = "report",
+            value_enum,
+            use_value_delimiter = true,
+            default_value = "gainers,tracked,daily"
+        )]
+        reports: Vec<Report>,
+
+        /// Filter expression of a tracked song, can be given more than once
+        #[clap(long = "track")]
+        tracks: Vec<String>,
+
+        /// Number of entries in the gainers report
+        #[clap(short, long, default_value = "20")]
+        limit: usize,
+
+        /// Output format of the region reports
+        #[clap(short, long, value_enum, default_value = "csv")]
+        format: Format,
+    },
+    /// Daily digest of the songs on a watchlist in every region
+    Digest {
+        /// Watchlist file, TOML or JSON
+        #[clap(short, long)]
+        watchlist: String,
+
+        /// Date
+        #[clap(short, long)]
+        date: String,
+
+        /// Output format
+        #[clap(short, long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    /// Evaluate alert rules on a date and deliver the new alerts
+    Alerts {
+        /// Alert config file, TOML or JSON
+        #[clap(short, long)]
+        config: String,
+
+        /// Date
+        #[clap(short, long)]
+        date: String,
+
+        /// State file of the alerts already sent, overrides the config
+        #[clap(short, long)]
+        state: Option<String>,
+    },
+    /// Atom feed of the daily #1s, movers and debuts
+    Feed {
+        /// Feed file, entries already in it are kept
+        #[clap(short, long)]
+        out: String,
+
+        /// Regions sharing an entry, as name=code,code, can be given more than once
+        #[clap(short, long = "group")]
+        groups: Vec<String>,
+
+        /// Number of entries in the feed
+        #[clap(short, long, default_value = "60")]
+        max: usize,
+
+        /// Address the feed is published at
+        #[clap(long)]
+        url: Option<String>,
+
+        /// Read every entry from the archive again
+        #[clap(long)]
+        rebuild: bool,
+    },
+    /// Render the chart archive as a static website
+    Site {
+        /// Directory the site is written to
+        #[clap(short, long)]
+        out: String,
+
+        /// Only these region codes, can be given more than once
+        #[clap(short, long = "code")]
+        codes: Vec<String>,
+
+        /// First date
+        #[clap(long)]
+        from: Option<String>,
+
+        /// Last date
+        #[clap(long)]
+        to: Option<String>,
     },
 }
 
@@ -99,7 +653,7 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
             ps,
             ts,
         } => {
-            let result: i64 = (spotify::parse_int(&ts)? - spotify::parse_int(&ps)?) as i64;
+            let result: i64 = spotify::parse_int(&ts)? - spotify::parse_int(&ps)?;
             println!("{}:", code);
             println!(
                 "#{}[{:+}] - {}({}{:+})",
@@ -119,65 +673,353 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
             keyword,
             gains,
             previous_date,
-        } => spotify::find::find(
-            code,
+            where_expr,
+        } => {
+            let filter = Filter::from_options(
+                title.as_deref(),
+                artist.as_deref(),
+                keyword.as_deref(),
+                where_expr.as_deref(),
+            )?
+            .ok_or("Either one of title, artist, keyword or where need to be specified!")?;
+
+            spotify::find::find(code, date, filter, all, gains, previous_date)?
+        }
+        Commands::Daily {
             date,
             title,
             artist,
-            all,
-            keyword,
-            gains,
-            previous_date,
-        )?,
-        Commands::Daily {
+            where_expr,
+            style,
+            spark,
+        } => {
+            let filter = Filter::from_options(
+                title.as_deref(),
+                artist.as_deref(),
+                None,
+                where_expr.as_deref(),
+            )?
+            .ok_or("Either one of title, artist or where need to be specified!")?;
+
+            spotify::daily(&date, &filter, style, spark)?
+        }
+        Commands::Artist {
+            artist,
+            date,
+            no_features,
+        } => spotify::summary::artist(&artist, &date, !no_features)?,
+        Commands::Matrix {
             date,
             title,
             artist,
+            where_expr,
+            value,
+            format,
         } => {
-            let mut gains: Vec<(SpotifyGain, String)> = Vec::new();
-            for code in spotify::regions::Regions::regions_vec() {
-                let region = code.to_region_string();
-                let code = String::from(code);
-
-                if let Ok(f) = spotify::resolve_file_handle(code.as_str(), &date) {
-                    let chart = SpotifyChart::from_reader(f, &date, &code)?;
-                    if let Ok(previous_chart) = chart.previous_day() {
-                        let gain = match (title.clone(), artist.clone()) {
-                            (None, None) => {
-                                return Err(From::from(
-                                    "Either one of title or artist need to be specified!",
-                                ))
-                            }
-                            (None, Some(artist)) => {
-                                chart.song_gain(&previous_chart, None, Some(&artist), None)
-                            }
-                            (Some(title), None) => {
-                                chart.song_gain(&previous_chart, Some(&title), None, None)
-                            }
-                            (Some(title), Some(artist)) => {
-                                chart.song_gain(&previous_chart, Some(&title), Some(&artist), None)
-                            }
-                        };
-
-                        if gain.today_rank != 0 {
-                            gains.push((gain, region));
-                        } else {
-                            eprintln!("{:#?}", gain);
-                        }
-                    } else {
-                        eprintln!("{} - Previous day[-] data missing!", code);
-                    }
-                } else {
-                    eprintln!("{} - Today[{}] data missing!", code, date);
+            let filter = Filter::from_options(
+                title.as_deref(),
+                artist.as_deref(),
+                None,
+                where_expr.as_deref(),
+            )?
+            .ok_or("Either one of title, artist or where need to be specified!")?;
+
+            spotify::matrix::matrix(&date, &filter, value, format)?
+        }
+        Commands::Stats {
+            code,
+            from,
+            to,
+            title,
+            artist,
+            where_expr,
+            format,
+        } => {
+            let filter = Filter::from_options(
+                title.as_deref(),
+                artist.as_deref(),
+                None,
+                where_expr.as_deref(),
+            )?;
+
+            spotify::stats::stats(&code, &from, &to, filter.as_ref(), format)?
+        }
+        Commands::History {
+            code,
+            from,
+            to,
+            title,
+            artist,
+            where_expr,
+            estimate,
+            moving_average,
+            week_over_week,
+            weekday_adjusted,
+            svg,
+            bars,
+            compare,
+            compare_code,
+            format,
+        } => {
+            let filter = Filter::from_options(
+                title.as_deref(),
+                artist.as_deref(),
+                None,
+                where_expr.as_deref(),
+            )?
+            .ok_or("Either one of title, artist or where need to be specified!")?;
+
+            let smoothing = Smoothing {
+                moving_average,
+                week_over_week,
+                weekday_adjusted,
+            };
+
+            let svg = SvgOptions {
+                out: svg,
+                bars,
+                compare: compare
+                    .iter()
+                    .map(|expr| Filter::parse(expr))
+                    .collect::<Result<Vec<Filter>, _>>()?,
+                compare_codes: compare_code,
+            };
+
+            spotify::history::history(
+                &code, &from, &to, &filter, estimate, smoothing, &svg, format,
+            )?
+        }
+        Commands::Project {
+            code,
+            from,
+            to,
+            title,
+            artist,
+            where_expr,
+            estimate,
+            model,
+            days,
+            base,
+            milestones,
+            format,
+        } => {
+            let filter = Filter::from_options(
+                title.as_deref(),
+                artist.as_deref(),
+                None,
+                where_expr.as_deref(),
+            )?
+            .ok_or("Either one of title, artist or where need to be specified!")?;
+
+            spotify::project::project(
+                &code,
+                &from,
+                &to,
+                &filter,
+                estimate,
+                model,
+                days,
+                base,
+                &milestones,
+                format,
+            )?
+        }
+        Commands::Records {
+            code,
+            from,
+            to,
+            categories,
+            artists,
+            no_features,
+            limit,
+            format,
+        } => {
+            let categories = if categories.is_empty() {
+                Category::all()
+            } else {
+                categories
+            };
+
+            spotify::records::records(
+                &code,
+                &from,
+                &to,
+                &categories,
+                artists,
+                !no_features,
+                limit,
+                format,
+            )?
+        }
+        Commands::Debuts {
+            code,
+            from,
+            to,
+            kind,
+            biggest,
+            by_artist,
+            limit,
+            format,
+        } => spotify::debut::debuts(
+            &code,
+            from.as_deref(),
+            to.as_deref(),
+            kind,
+            biggest,
+            by_artist,
+            limit,
+            format,
+        )?,
+        Commands::Anomalies {
+            code,
+            from,
+            to,
+            window,
+            threshold,
+            method,
+            title,
+            artist,
+            where_expr,
+            format,
+        } => {
+            let filter = Filter::from_options(
+                title.as_deref(),
+                artist.as_deref(),
+                None,
+                where_expr.as_deref(),
+            )?;
+
+            spotify::anomaly::anomalies(
+                &code,
+                &from,
+                &to,
+                window,
+                threshold,
+                method,
+                filter.as_ref(),
+                format,
+            )?
+        }
+        Commands::Aggregate {
+            code,
+            period,
+            from,
+            to,
+            title,
+            artist,
+            where_expr,
+            limit,
+            out,
+            format,
+        } => {
+            let (from, to) = match (period, from, to) {
+                (Some(period), _, _) => spotify::aggregate::period_range(&period)?,
+                (None, Some(from), Some(to)) => (from, to),
+                _ => {
+                    return Err(From::from(
+                        "Either a period or from and to need to be specified!",
+                    ))
                 }
-            }
+            };
+            let filter = Filter::from_options(
+                title.as_deref(),
+                artist.as_deref(),
+                None,
+                where_expr.as_deref(),
+            )?;
 
-            gains.sort_by_key(|(gain, _)| gain.today_streams);
-            gains.reverse();
-            gains
-                .into_iter()
-                .for_each(|(gain, region)| gain.print(region, spotify::fmt::style2));
+            spotify::aggregate::aggregate(
+                &code,
+                &from,
+                &to,
+                filter.as_ref(),
+                limit,
+                out.as_deref(),
+                format,
+            )?
         }
+        Commands::Combined {
+            date,
+            title,
+            artist,
+            where_expr,
+            limit,
+            format,
+        } => {
+            let filter = Filter::from_options(
+                title.as_deref(),
+                artist.as_deref(),
+                None,
+                where_expr.as_deref(),
+            )?;
+
+            spotify::combined::combined(&date, filter.as_ref(), limit, format)?
+        }
+        Commands::Validate {
+            code,
+            rows,
+            failed_only,
+            format,
+        } => spotify::integrity::validate(code.as_deref(), rows, failed_only, format)?,
+        Commands::Gaps {
+            code,
+            from,
+            to,
+            plan,
+            format,
+        } => spotify::gaps::gaps(
+            code.as_deref(),
+            from.as_deref(),
+            to.as_deref(),
+            plan.as_deref(),
+            format,
+        )?,
+        Commands::Serve { addr } => spotify::server::serve(&addr)?,
+        Commands::Tui { code, date } => spotify::tui::tui(code.as_deref(), date.as_deref())?,
+        Commands::Shell { code, date } => spotify::shell::shell(code.as_deref(), date.as_deref())?,
+        Commands::Watch {
+            out,
+            reports,
+            tracks,
+            limit,
+            format,
+        } => {
+            let tracks = tracks
+                .iter()
+                .map(|track| Filter::parse(track))
+                .collect::<Result<Vec<Filter>, _>>()?;
+
+            spotify::watch::watch(WatchOptions {
+                reports,
+                tracks,
+                out: out.into(),
+                format,
+                limit,
+            })?
+        }
+        Commands::Digest {
+            watchlist,
+            date,
+            format,
+        } => spotify::digest::digest(&watchlist, &date, format)?,
+        Commands::Alerts {
+            config,
+            date,
+            state,
+        } => spotify::alerts::alerts(&config, &date, state.as_deref())?,
+        Commands::Feed {
+            out,
+            groups,
+            max,
+            url,
+            rebuild,
+        } => spotify::feed::feed(&out, &groups, max, url.as_deref(), rebuild)?,
+        Commands::Site {
+            out,
+            codes,
+            from,
+            to,
+        } => spotify::site::site(&out, &codes, from.as_deref(), to.as_deref())?,
     }
 
     Ok(())
@@ -0,0 +1,223 @@
+use std::{collections::HashMap, error::Error, fs::File, io::Write};
+
+use super::{
+    filter::Filter,
+    fmt::{add_comma, write_report, Format},
+    regions,
+    validate::match_date,
+    SpotifyChart, SpotifyEntry,
+};
+use regions::RegionString;
+
+// First and last date of a period: a year "2022", a quarter "2022-Q2" or a
+// month "2022-05"
+pub fn period_range(period: &str) -> Result<(String, String), Box<dyn Error>> {
+    let invalid = || {
+        format!(
+            "Invalid period: \"{}\", expected 2022, 2022-Q2 or 2022-05",
+            period
+        )
+    };
+    let (year, rest) = match period.split_once('-') {
+        Some((year, rest)) => (year, Some(rest)),
+        None => (period, None),
+    };
+    let year: i32 = year.parse().map_err(|_| invalid())?;
+
+    let (first_month, last_month) = match rest {
+        None => (1, 12),
+        Some(quarter) if quarter.starts_with(['Q', 'q']) => match &quarter[1..] {
+            "1" => (1, 3),
+            "2" => (4, 6),
+            "3" => (7, 9),
+            "4" => (10, 12),
+            _ => return Err(From::from(invalid())),
+        },
+        Some(month) => {
+            let month: u8 = month.parse().map_err(|_| invalid())?;
+            if !(1..=12).contains(&month) {
+                return Err(From::from(invalid()));
+            }
+            (month, month)
+        }
+    };
+
+    let last_day = time::Month::try_from(last_month)?.length(year);
+    Ok((
+        format!("{}-{:02}-01", year, first_month),
+        format!("{}-{:02}-{:02}", year, last_month, last_day),
+    ))
+}
+
+impl SpotifyChart {
+    // Ranking of a period built by summing every track's daily streams. The
+    // result carries the region and last date of the charts.
+    pub fn aggregate(charts: &[SpotifyChart]) -> Result<Self, Box<dyn Error>> {
+        let last = charts.last().ok_or("No charts to aggregate!")?;
+
+        let mut order: Vec<(&str, &str)> = Vec::new();
+        let mut streams: HashMap<(&str, &str), i64> = HashMap::new();
+        for entry in charts.iter().flat_map(|chart| chart.chart.iter()) {
+            let key = (entry.title.as_str(), entry.artist.as_str());
+            let total = streams.entry(key).or_insert_with(|| {
+                order.push(key);
+                0
+            });
+            *total += entry.streams;
+        }
+
+        // Ties keep the order the tracks first charted in
+        order.sort_by_key(|key| std::cmp::Reverse(streams[key]));
+
+        let mut res = SpotifyChart::new();
+        res.chart = order
+            .into_iter()
+            .enumerate()
+            .map(|(i, key)| SpotifyEntry::new(i as i16 + 1, key.0, key.1, streams[&key]))
+            .collect();
+        res.region = regions::Regions::from(last.code.as_str()).to_region_string();
+        res.code = last.code.clone();
+        res.date_string = last.date_string.clone();
+        res.date = match_date(&last.date_string)?.unwrap();
+        res.count = res.chart.len();
+        Ok(res)
+    }
+
+    // Write the chart in the archive's own `*` delimited format
+    pub fn write_to<W: Write>(&self, w: W) -> Result<(), Box<dyn Error>> {
+        let mut csv_wtr = csv::WriterBuilder::new()
+            .has_headers(false)
+            .delimiter(b'*')
+            .from_writer(w);
+
+        for entry in &self.chart {
+            csv_wtr.write_record(&[
+                entry.rank.to_string(),
+                entry.title.clone(),
+                entry.artist.clone(),
+                add_comma(entry.streams),
+            ])?;
+        }
+        csv_wtr.flush()?;
+        Ok(())
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn aggregate(
+    code: &str,
+    from: &str,
+    to: &str,
+    filter: Option<&Filter>,
+    limit: Option<usize>,
+    out: Option<&str>,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let charts = SpotifyChart::from_code_range(code, from, to)?;
+    let mut chart = SpotifyChart::aggregate(&charts)?;
+    if let Some(limit) = limit {
+        chart.chart.truncate(limit);
+        chart.count = chart.chart.len();
+    }
+
+    if let Some(out) = out {
+        chart.write_to(File::create(out).map_err(|e| format!("{}: {}", e, out))?)?;
+        eprintln!("Wrote {} entries to {}", chart.count, out);
+    }
+
+    let entries: Vec<&SpotifyEntry> = match filter {
+        Some(filter) => chart.find_all_where(filter).unwrap_or_default(),
+        None => chart.chart.iter().collect(),
+    };
+
+    if format == Format::Text {
+        println!(
+            "Aggregate: code<{}> from<{}> to<{}> days<{}> - {} results",
+            code,
+            from,
+            to,
+            charts.len(),
+            entries.len()
+        );
+    }
+    write_report(
+        std::io::stdout(),
+        format,
+        &["Rank", "Title", "Artist", "Streams"],
+        &entries,
+        |entry| {
+            vec![
+                entry.rank.to_string(),
+                entry.title.clone(),
+                entry.artist.clone(),
+                add_comma(entry.streams),
+            ]
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spotify::fixtures::{chart, ranked};
+
+    type MyResult<T> = Result<T, Box<dyn Error>>;
+
+    #[test]
+    fn aggregate_sums_streams() -> MyResult<()> {
+        let charts = vec![
+            chart(
+                "us",
+                "2022-06-01",
+                ranked(&[("A", 300), ("B", 200), ("C", 100)]),
+            ),
+            chart("us", "2022-06-02", ranked(&[("B", 300), ("C", 250)])),
+        ];
+        let total = SpotifyChart::aggregate(&charts)?;
+
+        let ranking: Vec<(i16, &str, i64)> = total
+            .chart
+            .iter()
+            .map(|entry| (entry.rank, entry.title.as_str(), entry.streams))
+            .collect();
+        assert_eq!(vec![(1, "B", 500), (2, "C", 350), (3, "A", 300)], ranking);
+        assert_eq!("2022-06-02", total.date_string);
+        assert_eq!("US", total.region);
+        Ok(())
+    }
+
+    #[test]
+    fn write_and_read_back() -> MyResult<()> {
+        let chart = chart(
+            "us",
+            "2022-06-01",
+            ranked(&[("A * B", 1234567), ("C", 100)]),
+        );
+        let mut out = Vec::new();
+        chart.write_to(&mut out)?;
+        assert_eq!(
+            "1*\"A * B\"*Artist*1,234,567\n2*C*Artist*100\n",
+            String::from_utf8(out)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn periods() -> MyResult<()> {
+        assert_eq!(
+            ("2022-01-01".to_string(), "2022-12-31".to_string()),
+            period_range("2022")?
+        );
+        assert_eq!(
+            ("2022-04-01".to_string(), "2022-06-30".to_string()),
+            period_range("2022-Q2")?
+        );
+        assert_eq!(
+            ("2024-02-01".to_string(), "2024-02-29".to_string()),
+            period_range("2024-02")?
+        );
+        assert!(period_range("2022-13").is_err());
+        assert!(period_range("2022-Q5").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,668 @@
+use std::{
+    collections::BTreeSet,
+    error::Error,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    filter::Filter,
+    fmt::add_comma,
+    history::percent_string,
+    regions::Regions,
+    validate::{match_date, verify_code, verify_date},
+    SpotifyChart, SpotifyGain,
+};
+
+// Rules and where their alerts go, read from a TOML or JSON file:
+//
+//     regions = ["us", "gb"]
+//
+//     [deliver]
+//     webhook = "http://127.0.0.1:9000/alerts"
+//     file = "alerts.jsonl"
+//
+//     [[rule]]
+//     name = "As It Was top 10"
+//     title = "As It Was"
+//     enters_top = 10
+//
+//     [[rule]]
+//     name = "Big movers"
+//     streams_change = 20
+//
+//     [[rule]]
+//     name = "Joji debut"
+//     artist = "Joji"
+//     debut = true
+//
+// Without `regions` every region is checked, without `[deliver]` alerts go
+// to stdout.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertConfig {
+    #[serde(default)]
+    pub regions: Vec<String>,
+    // Alerts already sent, next to the config file by default
+    pub state: Option<PathBuf>,
+    #[serde(default)]
+    pub deliver: Delivery,
+    #[serde(rename = "rule", alias = "rules", default)]
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Delivery {
+    #[serde(default)]
+    pub stdout: bool,
+    // Alerts appended as JSON lines
+    pub file: Option<PathBuf>,
+    // Every alert is POSTed as a JSON object
+    pub webhook: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub name: Option<String>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    #[serde(rename = "where")]
+    pub where_expr: Option<String>,
+    // Rank the song has to reach from below or off the chart
+    pub enters_top: Option<i16>,
+    // Streams change against the previous day, in percent either way
+    pub streams_change: Option<f64>,
+    // New on the region's chart against the previous day
+    #[serde(default)]
+    pub debut: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Condition {
+    EntersTop(i16),
+    StreamsChange(f64),
+    Debut,
+}
+
+impl Condition {
+    pub fn holds(&self, gain: &SpotifyGain) -> bool {
+        match *self {
+            Condition::EntersTop(top) => {
+                gain.today_rank <= top && (gain.yesterday_rank == 0 || gain.yesterday_rank > top)
+            }
+            Condition::StreamsChange(percent) => {
+                gain.yesterday_rank != 0 && gain.percent_diff.abs() * 100f64 >= percent
+            }
+            Condition::Debut => gain.yesterday_rank == 0,
+        }
+    }
+
+    // Part of the alert keys, so two rules on one song stay apart
+    pub fn id(&self) -> String {
+        match self {
+            Condition::EntersTop(top) => format!("enters_top={}", top),
+            Condition::StreamsChange(percent) => format!("streams_change={}", percent),
+            Condition::Debut => "debut".to_string(),
+        }
+    }
+}
+
+impl Rule {
+    pub fn condition(&self) -> Result<Condition, Box<dyn Error>> {
+        match (self.enters_top, self.streams_change, self.debut) {
+            (Some(top), None, false) if top > 0 => Ok(Condition::EntersTop(top)),
+            (None, Some(percent), false) if percent >= 0f64 => {
+                Ok(Condition::StreamsChange(percent))
+            }
+            (None, None, true) => Ok(Condition::Debut),
+            (None, None, false) => Err(From::from(format!(
+                "Rule \"{}\" needs one of enters_top, streams_change or debut",
+                self.label()
+            ))),
+            (Some(_), None, false) | (None, Some(_), false) => Err(From::from(format!(
+                "Rule \"{}\" has an invalid threshold",
+                self.label()
+            ))),
+            _ => Err(From::from(format!(
+                "Rule \"{}\" can only have one of enters_top, streams_change or debut",
+                self.label()
+            ))),
+        }
+    }
+
+    // None matches every song
+    pub fn filter(&self) -> Result<Option<Filter>, Box<dyn Error>> {
+        Filter::from_options(
+            self.title.as_deref(),
+            self.artist.as_deref(),
+            None,
+            self.where_expr.as_deref(),
+        )
+    }
+
+    pub fn label(&self) -> String {
+        match (&self.name, &self.title, &self.artist, &self.where_expr) {
+            (Some(name), _, _, _) => name.clone(),
+            (None, Some(title), _, _) => title.clone(),
+            (None, None, Some(artist), _) => artist.clone(),
+            (None, None, None, Some(expr)) => expr.clone(),
+            (None, None, None, None) => "every song".to_string(),
+        }
+    }
+}
+
+impl AlertConfig {
+    // Parse by the file extension, `.json` or `.toml`
+    pub fn parse(text: &str, extension: &str) -> Result<Self, Box<dyn Error>> {
+        let config: AlertConfig = match extension {
+            "json" => serde_json::from_str(text)?,
+            "toml" => toml::from_str(text)?,
+            _ => {
+                return Err(From::from(format!(
+                    "Unknown alert config format: \"{}\", expected .toml or .json",
+                    extension
+                )))
+            }
+        };
+
+        if config.rules.is_empty() {
+            return Err(From::from("The alert config has no rules!"));
+        }
+        if let Some(code) = config.regions.iter().find(|code| !verify_code(code)) {
+            return Err(From::from(format!("Invalid code: \"{}\"", code)));
+        }
+        for rule in &config.rules {
+            rule.condition()?;
+            rule.filter()?;
+        }
+        Ok(config)
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", e, path))?;
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        AlertConfig::parse(&text, extension).map_err(|e| From::from(format!("{}: {}", path, e)))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub rule: String,
+    // Condition and filter of the rule, stays the same when it is renamed or
+    // other rules are added around it
+    #[serde(skip)]
+    pub rule_id: String,
+    pub region: String,
+    pub code: String,
+    pub date: String,
+    pub title: String,
+    pub artist: String,
+    pub rank: i16,
+    pub previous_rank: i16,
+    pub streams: i64,
+    pub streams_diff: i64,
+    pub percent_diff: f64,
+    pub message: String,
+}
+
+impl Alert {
+    // Same rule, chart and song is the same alert
+    pub fn key(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{}",
+            self.rule_id, self.code, self.date, self.title, self.artist
+        )
+    }
+}
+
+fn message(condition: Condition, gain: &SpotifyGain, region: &str) -> String {
+    let song = format!("{} - {}", gain.title, gain.artist);
+    match condition {
+        Condition::EntersTop(top) => format!(
+            "{} entered the top {} in {} at #{}",
+            song, top, region, gain.today_rank
+        ),
+        Condition::StreamsChange(_) => format!(
+            "{} streams {} in {} ({} at #{})",
+            song,
+            percent_string(gain.percent_diff),
+            region,
+            add_comma(gain.today_streams),
+            gain.today_rank
+        ),
+        Condition::Debut => format!("{} debuted in {} at #{}", song, region, gain.today_rank),
+    }
+}
+
+// Alerts of every rule in every pair of (today, previous day) charts
+pub fn evaluate(
+    rules: &[(String, Condition, Option<Filter>)],
+    charts: &[(SpotifyChart, SpotifyChart)],
+) -> Vec<Alert> {
+    let mut alerts = Vec::new();
+    for (name, condition, filter) in rules {
+        let rule_id = match filter {
+            Some(filter) => format!("{} where<{}>", condition.id(), filter),
+            None => condition.id(),
+        };
+        for (chart, previous_chart) in charts {
+            for gain in chart.gains(previous_chart) {
+                let matches = filter.as_ref().is_none_or(|filter| {
+                    chart
+                        .find_exact(&gain.title, &gain.artist)
+                        .is_some_and(|entry| filter.matches(entry))
+                });
+                if !matches || !condition.holds(&gain) {
+                    continue;
+                }
+                alerts.push(Alert {
+                    rule: name.clone(),
+                    rule_id: rule_id.clone(),
+                    region: chart.region.clone(),
+                    code: chart.code.clone(),
+                    date: chart.date_string.clone(),
+                    message: message(*condition, &gain, &chart.region),
+                    title: gain.title,
+                    artist: gain.artist,
+                    rank: gain.today_rank,
+                    previous_rank: gain.yesterday_rank,
+                    streams: gain.today_streams,
+                    streams_diff: gain.streams_diff,
+                    percent_diff: gain.percent_diff,
+                })
+            }
+        }
+    }
+    alerts
+}
+
+pub fn post_webhook(url: &str, alert: &Alert) -> Result<(), Box<dyn Error>> {
+    ureq::post(url)
+        .header("Content-Type", "application/json")
+        .send(serde_json::to_string(alert)?)
+        .map_err(|e| format!("{}: {}", url, e))?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    Webhook,
+    File,
+    Stdout,
+}
+
+impl Target {
+    // Part of the keys in the state file
+    fn name(&self) -> &'static str {
+        match self {
+            Target::Webhook => "webhook",
+            Target::File => "file",
+            Target::Stdout => "stdout",
+        }
+    }
+}
+
+impl Delivery {
+    // Stdout when nothing else is set
+    fn targets(&self) -> Vec<Target> {
+        let mut targets = Vec::new();
+        if self.webhook.is_some() {
+            targets.push(Target::Webhook);
+        }
+        if self.file.is_some() {
+            targets.push(Target::File);
+        }
+        if self.stdout || targets.is_empty() {
+            targets.push(Target::Stdout);
+        }
+        targets
+    }
+}
+
+fn deliver_to(target: Target, delivery: &Delivery, alert: &Alert) -> Result<(), Box<dyn Error>> {
+    match (target, &delivery.webhook, &delivery.file) {
+        (Target::Webhook, Some(url), _) => post_webhook(url, alert)?,
+        (Target::File, _, Some(path)) => {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("{}: {}", e, path.display()))?;
+            writeln!(file, "{}", serde_json::to_string(alert)?)?;
+        }
+        (Target::Stdout, _, _) => println!("[{}] {}", alert.rule, alert.message),
+        _ => (),
+    }
+    Ok(())
+}
+
+// Deliver an alert to every target it has not reached yet. Each target goes
+// into `sent` on its own, so when one fails the next run only retries that
+// one. Returns false when every target already had the alert.
+fn deliver(
+    delivery: &Delivery,
+    alert: &Alert,
+    sent: &mut BTreeSet<String>,
+) -> Result<bool, Box<dyn Error>> {
+    let mut new = false;
+    let mut errors = Vec::new();
+    for target in delivery.targets() {
+        let key = format!("{}|{}", target.name(), alert.key());
+        if sent.contains(&key) {
+            continue;
+        }
+        new = true;
+        match deliver_to(target, delivery, alert) {
+            Ok(()) => {
+                sent.insert(key);
+            }
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+    if errors.is_empty() {
+        Ok(new)
+    } else {
+        Err(From::from(errors.join(", ")))
+    }
+}
+
+fn load_state(path: &Path) -> Result<BTreeSet<String>, Box<dyn Error>> {
+    match fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map_err(|e| From::from(format!("{}: {}", path.display(), e))),
+        // No state yet on the first run
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeSet::new()),
+        Err(e) => Err(From::from(format!("{}: {}", e, path.display()))),
+    }
+}
+
+// Evaluate the rules on a date and deliver the alerts not sent before
+pub fn alerts(config_path: &str, date: &str, state: Option<&str>) -> Result<(), Box<dyn Error>> {
+    if !verify_date(date) || match_date(date).is_err() {
+        return Err(From::from(format!("Invalid date: \"{}\"", date)));
+    }
+    let config = AlertConfig::load(config_path)?;
+    let rules = config
+        .rules
+        .iter()
+        .map(|rule| Ok((rule.label(), rule.condition()?, rule.filter()?)))
+        .collect::<Result<Vec<(String, Condition, Option<Filter>)>, Box<dyn Error>>>()?;
+    let state_path = match (state, &config.state) {
+        (Some(state), _) => PathBuf::from(state),
+        (None, Some(state)) => state.clone(),
+        (None, None) => Path::new(config_path).with_extension("state.json"),
+    };
+    let mut sent = load_state(&state_path)?;
+
+    let codes: Vec<String> = if config.regions.is_empty() {
+        Regions::regions_vec()
+            .into_iter()
+            .map(String::from)
+            .collect()
+    } else {
+        config.regions.clone()
+    };
+    let mut charts = Vec::new();
+    for code in &codes {
+        let chart = match SpotifyChart::from_code_date(code, date) {
+            Ok(chart) => chart,
+            Err(_) => {
+                eprintln!("{} - Today[{}] data missing!", code, date);
+                continue;
+            }
+        };
+        match chart.previous_day() {
+            Ok(previous_chart) => charts.push((chart, previous_chart)),
+            Err(_) => eprintln!("{} - Previous day[-] data missing!", code),
+        }
+    }
+
+    let (mut delivered, mut skipped, mut failed) = (0, 0, 0);
+    for alert in evaluate(&rules, &charts) {
+        match deliver(&config.deliver, &alert, &mut sent) {
+            Ok(true) => delivered += 1,
+            Ok(false) => skipped += 1,
+            Err(e) => {
+                eprintln!("{} - {}", alert.message, e);
+                failed += 1;
+            }
+        }
+    }
+
+    fs::write(&state_path, serde_json::to_string_pretty(&sent)?)
+        .map_err(|e| format!("{}: {}", e, state_path.display()))?;
+    eprintln!(
+        "Alerts: date<{}> rules<{}> regions<{}> - {} delivered, {} already sent",
+        date,
+        rules.len(),
+        charts.len(),
+        delivered,
+        skipped
+    );
+    if failed > 0 {
+        return Err(From::from(format!(
+            "{} alerts could not be delivered",
+            failed
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spotify::fixtures::{chart, ranked};
+
+    #[test]
+    fn evaluate_rules() -> Result<(), Box<dyn Error>> {
+        let config = AlertConfig::parse(
+            r#"
+            [[rule]]
+            name = "top"
+            title = "C"
+            enters_top = 2
+
+            [[rule]]
+            name = "change"
+            streams_change = 20
+
+            [[rule]]
+            name = "debut"
+            debut = true
+            "#,
+            "toml",
+        )?;
+        let rules: Vec<(String, Condition, Option<Filter>)> = config
+            .rules
+            .iter()
+            .map(|rule| {
+                (
+                    rule.label(),
+                    rule.condition().unwrap(),
+                    rule.filter().unwrap(),
+                )
+            })
+            .collect();
+        let charts = vec![(
+            chart(
+                "us",
+                "2022-05-02",
+                ranked(&[("A", 1000), ("C", 900), ("D", 500)]),
+            ),
+            chart(
+                "us",
+                "2022-05-01",
+                ranked(&[("A", 950), ("B", 900), ("C", 700)]),
+            ),
+        )];
+        let alerts = evaluate(&rules, &charts);
+
+        let fired: Vec<(&str, &str)> = alerts
+            .iter()
+            .map(|alert| (alert.rule.as_str(), alert.title.as_str()))
+            .collect();
+        assert_eq!(vec![("top", "C"), ("change", "C"), ("debut", "D")], fired);
+        assert_eq!(
+            "C - Artist entered the top 2 in US at #2",
+            alerts[0].message
+        );
+        assert_eq!("streams_change=20|us|2022-05-02|C|Artist", alerts[1].key());
+        Ok(())
+    }
+
+    #[test]
+    fn unnamed_rules_on_one_song_keep_apart() -> Result<(), Box<dyn Error>> {
+        let config = AlertConfig::parse(
+            r#"
+            [[rule]]
+            title = "C"
+            enters_top = 2
+
+            [[rule]]
+            title = "C"
+            streams_change = 20
+            "#,
+            "toml",
+        )?;
+        let rules: Vec<(String, Condition, Option<Filter>)> = config
+            .rules
+            .iter()
+            .map(|rule| {
+                (
+                    rule.label(),
+                    rule.condition().unwrap(),
+                    rule.filter().unwrap(),
+                )
+            })
+            .collect();
+        let charts = vec![(
+            chart("us", "2022-05-02", ranked(&[("A", 1000), ("C", 900)])),
+            chart(
+                "us",
+                "2022-05-01",
+                ranked(&[("A", 950), ("B", 900), ("C", 700)]),
+            ),
+        )];
+        let keys: BTreeSet<String> = evaluate(&rules, &charts)
+            .iter()
+            .map(|alert| alert.key())
+            .collect();
+        assert_eq!(2, keys.len());
+        Ok(())
+    }
+
+    #[test]
+    fn rule_ids_follow_content() -> Result<(), Box<dyn Error>> {
+        let charts = vec![(
+            chart("us", "2022-05-02", ranked(&[("A", 1000), ("C", 900)])),
+            chart("us", "2022-05-01", ranked(&[("B", 900)])),
+        )];
+        let debut_of_c = (
+            "C".to_string(),
+            Condition::Debut,
+            Some(Filter::parse("title:C")?),
+        );
+        let keys = |rules: &[(String, Condition, Option<Filter>)]| -> Vec<String> {
+            evaluate(rules, &charts)
+                .iter()
+                .filter(|alert| alert.rule == "C")
+                .map(|alert| alert.key())
+                .collect()
+        };
+
+        // A rule added in front leaves the key of the next one alone
+        let alone = keys(std::slice::from_ref(&debut_of_c));
+        let behind = keys(&[("all".to_string(), Condition::Debut, None), debut_of_c]);
+        assert_eq!(alone, behind);
+        Ok(())
+    }
+
+    #[test]
+    fn reject_bad_date() {
+        assert!(alerts("unused.toml", "junk", None).is_err());
+    }
+
+    #[test]
+    fn retry_only_failed_targets() -> Result<(), Box<dyn Error>> {
+        let charts = vec![(
+            chart("gb", "2022-05-02", ranked(&[("A", 1000)])),
+            chart("gb", "2022-05-01", ranked(&[])),
+        )];
+        let rules = vec![("debut".to_string(), Condition::Debut, None)];
+        let alert = &evaluate(&rules, &charts)[0];
+        let dir = std::env::temp_dir().join(format!("chart-alerts-{}", std::process::id()));
+        let mut delivery = Delivery {
+            stdout: true,
+            file: Some(dir.join("alerts.jsonl")),
+            webhook: None,
+        };
+        let mut sent = BTreeSet::new();
+
+        // The directory is not there yet, only stdout gets the alert
+        assert!(deliver(&delivery, alert, &mut sent).is_err());
+        assert_eq!(
+            vec![format!("stdout|{}", alert.key())],
+            Vec::from_iter(sent.clone())
+        );
+
+        fs::create_dir_all(&dir)?;
+        assert!(deliver(&delivery, alert, &mut sent)?);
+        assert_eq!(2, sent.len());
+        assert!(!deliver(&delivery, alert, &mut sent)?);
+        assert_eq!(
+            1,
+            fs::read_to_string(dir.join("alerts.jsonl"))?
+                .lines()
+                .count()
+        );
+
+        delivery.stdout = false;
+        assert!(!deliver(&delivery, alert, &mut sent)?);
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn reject_bad_rules() {
+        let rule = |body: &str| AlertConfig::parse(&format!("[[rule]]\n{}", body), "toml");
+        assert!(rule("title = \"A\"").is_err());
+        assert!(rule("enters_top = 10\ndebut = true").is_err());
+        assert!(rule("enters_top = 0").is_err());
+        assert!(rule("debut = true").is_ok());
+    }
+
+    #[test]
+    fn webhook_to_local_receiver() -> Result<(), Box<dyn Error>> {
+        let server = tiny_http::Server::http("127.0.0.1:0").map_err(|e| e.to_string())?;
+        let url = format!("http://{}/alerts", server.server_addr());
+        let receiver = std::thread::spawn(move || {
+            let mut request = server.recv().unwrap();
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body).unwrap();
+            request.respond(tiny_http::Response::empty(204)).unwrap();
+            body
+        });
+
+        let charts = vec![(
+            chart("gb", "2022-05-02", ranked(&[("A", 1000)])),
+            SpotifyChart::new(),
+        )];
+        let rules = vec![("debut".to_string(), Condition::Debut, None)];
+        let alert = &evaluate(&rules, &charts)[0];
+        post_webhook(&url, alert)?;
+
+        let body: serde_json::Value = serde_json::from_str(&receiver.join().unwrap())?;
+        assert_eq!("debut", body["rule"]);
+        assert_eq!("gb", body["code"]);
+        assert_eq!(1, body["rank"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,275 @@
+use std::{collections::HashMap, error::Error};
+
+use serde::Serialize;
+
+use super::{
+    add_days, date_range,
+    filter::Filter,
+    fmt::{add_comma, add_comma_plus, write_report, Format},
+    history::percent_string,
+    SpotifyChart, SpotifyGain,
+};
+
+// Fewest earlier changes a track needs before it can be scored
+const MIN_POINTS: usize = 5;
+// Ranks on each side of an entry that count as its peers
+const PEER_RANKS: i16 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Method {
+    // Robust z-score from the median and median absolute deviation
+    Mad,
+    // Classic z-score from the mean and standard deviation
+    Zscore,
+}
+
+impl Method {
+    // How many deviations `value` is away from the center of `window`, None
+    // when the window is too small or has no spread at all
+    pub fn score(&self, window: &[f64], value: f64) -> Option<f64> {
+        if window.len() < MIN_POINTS {
+            return None;
+        }
+
+        let (center, spread) = match self {
+            Method::Mad => {
+                let center = median(window);
+                let deviations: Vec<f64> = window.iter().map(|x| (x - center).abs()).collect();
+                // 1.4826 scales the MAD to a standard deviation for normal data
+                (center, 1.4826 * median(&deviations))
+            }
+            Method::Zscore => {
+                let n = window.len() as f64;
+                let mean = window.iter().sum::<f64>() / n;
+                let variance = window.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1f64);
+                (mean, variance.sqrt())
+            }
+        };
+
+        // Treat rounding noise as no spread
+        if spread > 1e-12 {
+            Some((value - center) / spread)
+        } else {
+            None
+        }
+    }
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2f64
+    } else {
+        sorted[mid]
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Anomaly {
+    pub date: String,
+    pub title: String,
+    pub artist: String,
+    pub today_rank: i16,
+    pub yesterday_rank: i16,
+    pub today_streams: i64,
+    pub streams_diff: i64,
+    pub percent_diff: f64,
+    // Score against the track's own recent changes
+    pub score: f64,
+    // Score against the same day's changes of the entries around its rank
+    pub peer_score: Option<f64>,
+}
+
+// Score every day-over-day change from the chart at index `start` on against
+// the `window` changes before it. Only entries on both charts have a change.
+pub fn detect(
+    charts: &[SpotifyChart],
+    start: usize,
+    window: usize,
+    threshold: f64,
+    method: Method,
+    filter: Option<&Filter>,
+) -> Vec<Anomaly> {
+    let mut changes: HashMap<(String, String), Vec<f64>> = HashMap::new();
+    let mut anomalies = Vec::new();
+
+    for day in 1..charts.len() {
+        let (previous, chart) = (&charts[day - 1], &charts[day]);
+        let gains: Vec<SpotifyGain> = chart
+            .chart
+            .iter()
+            .filter_map(|entry| {
+                let yesterday = previous.find_exact(&entry.title, &entry.artist)?;
+                SpotifyGain::from_spotify_entry(entry, yesterday).ok()
+            })
+            .collect();
+
+        for gain in &gains {
+            let key = (gain.title.clone(), gain.artist.clone());
+            let history = changes.entry(key).or_default();
+            let recent = &history[history.len().saturating_sub(window)..];
+
+            let wanted = filter.is_none_or(|filter| {
+                chart
+                    .find_exact(&gain.title, &gain.artist)
+                    .is_some_and(|entry| filter.matches(entry))
+            });
+            if day >= start && wanted {
+                if let Some(score) = method.score(recent, gain.percent_diff) {
+                    if score.abs() >= threshold {
+                        let peers: Vec<f64> = gains
+                            .iter()
+                            .filter(|peer| {
+                                (peer.title != gain.title || peer.artist != gain.artist)
+                                    && (peer.today_rank - gain.today_rank).abs() <= PEER_RANKS
+                            })
+                            .map(|peer| peer.percent_diff)
+                            .collect();
+
+                        anomalies.push(Anomaly {
+                            date: chart.date_string.clone(),
+                            title: gain.title.clone(),
+                            artist: gain.artist.clone(),
+                            today_rank: gain.today_rank,
+                            yesterday_rank: gain.yesterday_rank,
+                            today_streams: gain.today_streams,
+                            streams_diff: gain.streams_diff,
+                            percent_diff: gain.percent_diff,
+                            score,
+                            peer_score: method.score(&peers, gain.percent_diff),
+                        });
+                    }
+                }
+            }
+
+            if gain.percent_diff.is_finite() {
+                history.push(gain.percent_diff);
+            }
+        }
+    }
+
+    anomalies
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn anomalies(
+    code: &str,
+    from: &str,
+    to: &str,
+    window: usize,
+    threshold: f64,
+    method: Method,
+    filter: Option<&Filter>,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    // Checks the range before the window is subtracted from it
+    date_range(from, to)?;
+    // Load the window before the range as well so the first days can be scored
+    let charts = SpotifyChart::from_code_range(code, &add_days(from, -(window as i64) - 1)?, to)?;
+    let start = charts
+        .iter()
+        .position(|chart| chart.date_string.as_str() >= from)
+        .unwrap_or(charts.len());
+
+    let mut rows = detect(&charts, start, window, threshold, method, filter);
+    rows.sort_by(|a, b| b.score.abs().total_cmp(&a.score.abs()));
+
+    if format == Format::Text {
+        println!(
+            "Anomalies: code<{}> from<{}> to<{}> method<{:?}> threshold<{}> - {} results",
+            code,
+            from,
+            to,
+            method,
+            threshold,
+            rows.len()
+        );
+    }
+    write_report(
+        std::io::stdout(),
+        format,
+        &[
+            "Date", "Rank", "Title", "Artist", "Streams", "Change", "%", "Score", "Peers",
+        ],
+        &rows,
+        |row| {
+            vec![
+                row.date.clone(),
+                format!(
+                    "{} [{:+}]",
+                    row.today_rank,
+                    row.yesterday_rank - row.today_rank
+                ),
+                row.title.clone(),
+                row.artist.clone(),
+                add_comma(row.today_streams),
+                add_comma_plus(row.streams_diff),
+                percent_string(row.percent_diff),
+                format!("{:+.1}", row.score),
+                row.peer_score
+                    .map(|score| format!("{:+.1}", score))
+                    .unwrap_or_else(|| "-".to_string()),
+            ]
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spotify::{fixtures::chart, SpotifyEntry};
+
+    #[test]
+    fn peers_with_the_same_title() {
+        // The first song jumps on the last day, the others grow steadily. One
+        // of them has the same title by another artist and is still a peer.
+        let first = [1000, 1010, 1000, 1020, 1005, 1015, 1000, 2000];
+        let charts: Vec<SpotifyChart> = (0..first.len())
+            .map(|day| {
+                let mut entries = vec![SpotifyEntry::new(1, "Hit", "A", first[day])];
+                for i in 1..=5 {
+                    let title = if i == 1 {
+                        "Hit".to_string()
+                    } else {
+                        format!("Song {}", i)
+                    };
+                    let streams = 1000 + 10 * i * day as i64;
+                    entries.push(SpotifyEntry::new(i as i16 + 1, &title, "B", streams));
+                }
+                chart("us", &format!("2022-06-{:02}", day + 1), entries)
+            })
+            .collect();
+
+        let anomalies = detect(&charts, 7, 10, 3.0, Method::Mad, None);
+        let jump = anomalies
+            .iter()
+            .find(|anomaly| anomaly.artist == "A")
+            .unwrap();
+        assert_eq!("2022-06-08", jump.date);
+        // Five peers are enough for a score, four would not be
+        assert!(jump.peer_score.is_some_and(|score| score > 10f64));
+    }
+
+    #[test]
+    fn mad_score() {
+        let window = [0.01, -0.02, 0.0, 0.02, -0.01, 0.01, -0.01];
+        let score = Method::Mad.score(&window, 0.8).unwrap();
+        assert!(score > 10f64);
+        assert!(Method::Mad.score(&window, 0.0).unwrap().abs() < 1f64);
+    }
+
+    #[test]
+    fn score_needs_spread() {
+        assert_eq!(None, Method::Zscore.score(&[0.1; 10], 0.5));
+        assert_eq!(None, Method::Mad.score(&[0.1, 0.2], 0.5));
+    }
+
+    #[test]
+    fn median_even_odd() {
+        assert_eq!(2f64, median(&[3f64, 1f64, 2f64]));
+        assert_eq!(2.5, median(&[4f64, 1f64, 3f64, 2f64]));
+    }
+}
@@ -0,0 +1,241 @@
+use std::{fs, io::ErrorKind, sync::OnceLock};
+
+use regex::Regex;
+
+use super::data_dir;
+
+// Credited artists parsed out of the raw chart strings. The chart lists
+// co-leads comma separated in the artist column ("Bad Bunny, Chencho Corleone"),
+// features are either appended to it ("X feat. Y") or part of the title
+// ("Title (feat. Y)", "Title (with Y)"). The chart has no separate credit
+// fields, so the split is a best guess: a name holding a comma or " & " of its
+// own is only kept whole when it is listed in KEEP_WHOLE_FILE.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    Lead,
+    Featured,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Credit {
+    pub name: String,
+    pub role: Role,
+}
+
+impl Credit {
+    fn new(name: &str, role: Role) -> Self {
+        Credit {
+            name: name.to_string(),
+            role,
+        }
+    }
+
+    // Case-insensitive substring match on the name, optionally leads only
+    pub fn matches(&self, artist: &str, features: bool) -> bool {
+        (features || self.role == Role::Lead)
+            && self.name.to_lowercase().contains(&artist.to_lowercase())
+    }
+}
+
+fn artist_feature_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?i)\s+(?:feat\.?|ft\.?|featuring)\s+").unwrap())
+}
+
+fn title_feature_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?i)[(\[]\s*(?:feat\.?|ft\.?|featuring|with)\s+([^)\]]+)[)\]]").unwrap()
+    })
+}
+
+// File in the data directory listing the artists whose name holds a separator
+// of its own, one per line. Blank lines and lines starting with # are skipped.
+const KEEP_WHOLE_FILE: &str = "keep-whole.txt";
+
+// Used when the data directory has no KEEP_WHOLE_FILE
+const DEFAULT_KEEP_WHOLE: [&str; 6] = [
+    "Tyler, The Creator",
+    "Earth, Wind & Fire",
+    "Crosby, Stills, Nash & Young",
+    "Emerson, Lake & Palmer",
+    "Blood, Sweat & Tears",
+    "Peter, Paul and Mary",
+];
+
+fn read_keep_whole(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+// Names kept whole, read from the data directory once
+fn keep_whole() -> &'static [String] {
+    static NAMES: OnceLock<Vec<String>> = OnceLock::new();
+    NAMES.get_or_init(|| {
+        let path = data_dir().join(KEEP_WHOLE_FILE);
+        match fs::read_to_string(&path) {
+            Ok(text) => read_keep_whole(&text),
+            Err(e) => {
+                if e.kind() != ErrorKind::NotFound {
+                    eprintln!("{}: {}", e, path.display());
+                }
+                DEFAULT_KEEP_WHOLE
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect()
+            }
+        }
+    })
+}
+
+// Names separated by commas, and by " & " when `ampersand` is set. A name in
+// `keep` is not split.
+fn split_names<'a>(names: &'a str, ampersand: bool, keep: &[String]) -> Vec<&'a str> {
+    let mut split = Vec::new();
+    let mut rest = names;
+    while !rest.is_empty() {
+        let known = keep.iter().find(|name| {
+            rest.trim_start()
+                .get(..name.len())
+                .is_some_and(|start| start.eq_ignore_ascii_case(name))
+        });
+        let end = match known {
+            Some(name) => rest.len() - rest.trim_start().len() + name.len(),
+            None => {
+                let comma = rest.find(',');
+                let and = rest.find(" & ").filter(|_| ampersand);
+                comma.into_iter().chain(and).min().unwrap_or(rest.len())
+            }
+        };
+        split.push(rest[..end].trim());
+        rest = rest[end..].trim_start_matches([',', ' ', '&']);
+    }
+    split.retain(|name| !name.is_empty());
+    split
+}
+
+// Ordered list of credits: leads first as listed, then features. An artist is
+// only credited once, with the first role found.
+pub fn parse_credits(title: &str, artist: &str) -> Vec<Credit> {
+    let mut credits: Vec<Credit> = Vec::new();
+    let mut push = |name: &str, role: Role| {
+        if !credits
+            .iter()
+            .any(|credit| credit.name.eq_ignore_ascii_case(name))
+        {
+            credits.push(Credit::new(name, role));
+        }
+    };
+
+    let keep = keep_whole();
+    let mut parts = artist_feature_re().splitn(artist, 2);
+    if let Some(leads) = parts.next() {
+        split_names(leads, false, keep)
+            .into_iter()
+            .for_each(|name| push(name, Role::Lead));
+    }
+    if let Some(features) = parts.next() {
+        split_names(features, true, keep)
+            .into_iter()
+            .for_each(|name| push(name, Role::Featured));
+    }
+
+    for caps in title_feature_re().captures_iter(title) {
+        split_names(&caps[1], true, keep)
+            .into_iter()
+            .for_each(|name| push(name, Role::Featured));
+    }
+
+    credits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(credits: &[Credit], role: Role) -> Vec<&str> {
+        credits
+            .iter()
+            .filter(|credit| credit.role == role)
+            .map(|credit| credit.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn co_leads() {
+        let credits = parse_credits("Me Porto Bonito", "Bad Bunny, Chencho Corleone");
+        assert_eq!(
+            names(&credits, Role::Lead),
+            vec!["Bad Bunny", "Chencho Corleone"]
+        );
+        assert!(names(&credits, Role::Featured).is_empty());
+    }
+
+    #[test]
+    fn names_with_commas() {
+        let credits = parse_credits(
+            "Song (feat. Earth, Wind & Fire)",
+            "Tyler, The Creator, Kali Uchis",
+        );
+        assert_eq!(
+            names(&credits, Role::Lead),
+            vec!["Tyler, The Creator", "Kali Uchis"]
+        );
+        assert_eq!(names(&credits, Role::Featured), vec!["Earth, Wind & Fire"]);
+    }
+
+    #[test]
+    fn listed_names_kept_whole() {
+        let keep = read_keep_whole("# Duos\n\n  Simon & Garfunkel \nNo, Not Split\n");
+        assert_eq!(vec!["Simon & Garfunkel", "No, Not Split"], keep);
+        assert_eq!(
+            vec!["Simon & Garfunkel", "Y"],
+            split_names("Simon & Garfunkel & Y", true, &keep)
+        );
+        // Matched regardless of case, the chart's spelling is kept
+        assert_eq!(
+            vec!["no, not split", "Other"],
+            split_names("no, not split, Other", false, &keep)
+        );
+        // Not listed, so split at every separator
+        assert_eq!(
+            vec!["Tyler", "The Creator"],
+            split_names("Tyler, The Creator", false, &keep)
+        );
+    }
+
+    #[test]
+    fn artist_feat() {
+        let credits = parse_credits("Song", "X feat. Y & Z");
+        assert_eq!(names(&credits, Role::Lead), vec!["X"]);
+        assert_eq!(names(&credits, Role::Featured), vec!["Y", "Z"]);
+    }
+
+    #[test]
+    fn title_feat() {
+        let credits = parse_credits("Left and Right (Feat. Jung Kook of BTS)", "Charlie Puth");
+        assert_eq!(names(&credits, Role::Lead), vec!["Charlie Puth"]);
+        assert_eq!(names(&credits, Role::Featured), vec!["Jung Kook of BTS"]);
+    }
+
+    #[test]
+    fn title_with_duplicate() {
+        let credits = parse_credits("STAY (with Justin Bieber)", "The Kid LAROI, Justin Bieber");
+        assert_eq!(
+            names(&credits, Role::Lead),
+            vec!["The Kid LAROI", "Justin Bieber"]
+        );
+        assert!(names(&credits, Role::Featured).is_empty());
+    }
+
+    #[test]
+    fn credit_matches() {
+        let credits = parse_credits("Song (feat. Drake)", "Future");
+        assert!(credits.iter().any(|credit| credit.matches("drake", true)));
+        assert!(!credits.iter().any(|credit| credit.matches("drake", false)));
+    }
+}
@@ -0,0 +1,190 @@
+use std::{collections::HashMap, error::Error};
+
+use serde::Serialize;
+
+use super::{
+    filter::Filter,
+    fmt::{add_comma, write_report, Format},
+    regions::Regions,
+    SpotifyChart,
+};
+
+#[derive(Debug, Serialize)]
+pub struct CombinedEntry {
+    pub rank: i16,
+    pub title: String,
+    pub artist: String,
+    // Sum of the track's streams over the tracked regions
+    pub streams: i64,
+    // Codes of the regions the track charts in
+    pub markets: Vec<String>,
+    pub global_rank: Option<i16>,
+    pub global_streams: Option<i64>,
+    // Share of the global streams coming from the tracked regions, None when
+    // the track is not on the global chart
+    pub global_share: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CombinedChart {
+    pub date: String,
+    // Regional charts that went into the ranking
+    pub regions: usize,
+    pub entries: Vec<CombinedEntry>,
+}
+
+impl CombinedChart {
+    // Ranking of the summed streams of every regional chart. The global chart
+    // is not summed, it is only looked up for the global share.
+    pub fn from_charts(charts: &[SpotifyChart]) -> Option<Self> {
+        let global = charts
+            .iter()
+            .find(|chart| Regions::from(chart.code.as_str()) == Regions::GLOBAL);
+        let regional: Vec<&SpotifyChart> = charts
+            .iter()
+            .filter(|chart| Regions::from(chart.code.as_str()) != Regions::GLOBAL)
+            .collect();
+        let date = regional.first()?.date_string.clone();
+
+        let mut order: Vec<(&str, &str)> = Vec::new();
+        let mut totals: HashMap<(&str, &str), (i64, Vec<String>)> = HashMap::new();
+        for chart in &regional {
+            for entry in &chart.chart {
+                let key = (entry.title.as_str(), entry.artist.as_str());
+                let total = totals.entry(key).or_insert_with(|| {
+                    order.push(key);
+                    (0, Vec::new())
+                });
+                total.0 += entry.streams;
+                total.1.push(chart.code.clone());
+            }
+        }
+        order.sort_by_key(|key| std::cmp::Reverse(totals[key].0));
+
+        let entries = order
+            .into_iter()
+            .enumerate()
+            .map(|(i, key)| {
+                let (streams, markets) = totals.remove(&key).unwrap();
+                let global_entry = global.and_then(|global| global.find_exact(key.0, key.1));
+                CombinedEntry {
+                    rank: i as i16 + 1,
+                    title: key.0.to_string(),
+                    artist: key.1.to_string(),
+                    streams,
+                    markets,
+                    global_rank: global_entry.map(|entry| entry.rank),
+                    global_streams: global_entry.map(|entry| entry.streams),
+                    global_share: global_entry
+                        .filter(|entry| entry.streams > 0)
+                        .map(|entry| streams as f64 / entry.streams as f64),
+                }
+            })
+            .collect();
+
+        Some(CombinedChart {
+            date,
+            regions: regional.len(),
+            entries,
+        })
+    }
+}
+
+pub fn combined(
+    date: &str,
+    filter: Option<&Filter>,
+    limit: Option<usize>,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let charts = SpotifyChart::from_date_all(date);
+    let combined = CombinedChart::from_charts(&charts)
+        .ok_or(format!("No regional chart data found for {}", date))?;
+
+    // Filters are matched against the track's entry on any regional chart
+    let mut rows: Vec<&CombinedEntry> = combined
+        .entries
+        .iter()
+        .filter(|row| {
+            filter.is_none_or(|filter| {
+                charts.iter().any(|chart| {
+                    Regions::from(chart.code.as_str()) != Regions::GLOBAL
+                        && chart
+                            .find_exact(&row.title, &row.artist)
+                            .is_some_and(|entry| filter.matches(entry))
+                })
+            })
+        })
+        .collect();
+    if let Some(limit) = limit {
+        rows.truncate(limit);
+    }
+
+    if format == Format::Text {
+        println!(
+            "Combined: date<{}> regions<{}> - {} results",
+            date,
+            combined.regions,
+            rows.len()
+        );
+    }
+    write_report(
+        std::io::stdout(),
+        format,
+        &[
+            "Rank",
+            "Title",
+            "Artist",
+            "Streams",
+            "Markets",
+            "Global",
+            "Global streams",
+            "Share",
+        ],
+        &rows,
+        |row| {
+            vec![
+                row.rank.to_string(),
+                row.title.clone(),
+                row.artist.clone(),
+                add_comma(row.streams),
+                row.markets.len().to_string(),
+                row.global_rank
+                    .map(|rank| rank.to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                row.global_streams
+                    .map(add_comma)
+                    .unwrap_or_else(|| "-".to_string()),
+                row.global_share
+                    .map(|share| format!("{:.1}%", share * 100f64))
+                    .unwrap_or_else(|| "-".to_string()),
+            ]
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spotify::fixtures::{chart, ranked};
+
+    #[test]
+    fn combine_regions() {
+        let charts = vec![
+            chart("global", "2022-06-01", ranked(&[("A", 1000), ("B", 800)])),
+            chart("us", "2022-06-01", ranked(&[("A", 300), ("B", 200)])),
+            chart("gb", "2022-06-01", ranked(&[("B", 250), ("C", 100)])),
+        ];
+        let combined = CombinedChart::from_charts(&charts).unwrap();
+
+        assert_eq!(2, combined.regions);
+        let first = &combined.entries[0];
+        assert_eq!(("B", 450), (first.title.as_str(), first.streams));
+        assert_eq!(vec!["us", "gb"], first.markets);
+        assert_eq!(Some(2), first.global_rank);
+        assert_eq!(Some(450f64 / 800f64), first.global_share);
+
+        let last = &combined.entries[2];
+        assert_eq!(("C", 3), (last.title.as_str(), last.rank));
+        assert_eq!(None, last.global_share);
+    }
+}
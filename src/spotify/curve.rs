@@ -0,0 +1,97 @@
+use super::SpotifyChart;
+
+// Power-law fit of a chart's streams by rank: streams = a * rank^(-b), fitted
+// with least squares on ln(streams) = ln(a) - b * ln(rank)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankCurve {
+    pub a: f64,
+    pub b: f64,
+    // Last rank of the chart and its streams, nothing below the chart can
+    // have more streams than this
+    pub last_rank: i16,
+    pub floor: i64,
+}
+
+impl RankCurve {
+    pub fn fit(points: &[(i16, i64)]) -> Option<Self> {
+        let logs: Vec<(f64, f64)> = points
+            .iter()
+            .filter(|&&(rank, streams)| rank > 0 && streams > 0)
+            .map(|&(rank, streams)| ((rank as f64).ln(), (streams as f64).ln()))
+            .collect();
+        if logs.len() < 2 {
+            return None;
+        }
+
+        let n = logs.len() as f64;
+        let mean_x = logs.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = logs.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let cov: f64 = logs.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+        let var: f64 = logs.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+        if var == 0f64 {
+            return None;
+        }
+
+        let b = -cov / var;
+        let a = (mean_y + b * mean_x).exp();
+        let &(last_rank, floor) = points.iter().max_by_key(|(rank, _)| *rank)?;
+
+        Some(RankCurve {
+            a,
+            b,
+            last_rank,
+            floor,
+        })
+    }
+
+    pub fn streams_at(&self, rank: f64) -> f64 {
+        self.a * rank.powf(-self.b)
+    }
+
+    pub fn rank_at(&self, streams: f64) -> f64 {
+        (self.a / streams).powf(1f64 / self.b)
+    }
+
+    // Estimated streams of a song just below the chart. This is an upper
+    // bound, the song could be anywhere under the last rank.
+    pub fn off_chart_estimate(&self) -> i64 {
+        let estimate = self.streams_at(self.last_rank as f64 + 1f64) as i64;
+        estimate.clamp(0, self.floor)
+    }
+}
+
+impl SpotifyChart {
+    pub fn rank_curve(&self) -> Option<RankCurve> {
+        let points: Vec<(i16, i64)> = self
+            .chart
+            .iter()
+            .map(|entry| (entry.rank, entry.streams))
+            .collect();
+        RankCurve::fit(&points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_exact_power_law() {
+        let points: Vec<(i16, i64)> = (1..=200)
+            .map(|rank| (rank, (1_000_000f64 * (rank as f64).powf(-0.6)) as i64))
+            .collect();
+        let curve = RankCurve::fit(&points).unwrap();
+
+        assert!((curve.b - 0.6).abs() < 1e-3);
+        assert!((curve.a - 1_000_000f64).abs() / 1_000_000f64 < 1e-3);
+        assert!((curve.rank_at(curve.streams_at(50f64)) - 50f64).abs() < 1e-6);
+        assert_eq!(200, curve.last_rank);
+        assert!(curve.off_chart_estimate() <= curve.floor);
+    }
+
+    #[test]
+    fn fit_too_few_points() {
+        assert!(RankCurve::fit(&[(1, 100)]).is_none());
+        assert!(RankCurve::fit(&[]).is_none());
+    }
+}
@@ -0,0 +1,326 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+};
+
+use serde::Serialize;
+
+use super::{
+    add_days, available_dates,
+    fmt::{add_comma, write_report, Format},
+    SpotifyChart,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum DebutKind {
+    // First time on the chart
+    Debut,
+    // Charted before, dropped off and came back
+    ReEntry,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DebutRow {
+    pub date: String,
+    pub title: String,
+    pub artist: String,
+    pub rank: i16,
+    pub streams: i64,
+    pub kind: DebutKind,
+    // Last chart date before a re-entry
+    pub last_seen: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ArtistDebuts {
+    pub artist: String,
+    pub debuts: usize,
+    pub best_rank: i16,
+    pub streams: i64,
+}
+
+// Every entry that is not on the chart of the previous calendar day. A chart
+// without its previous day is only used as a starting point, since a song new
+// on it may have entered on the missing day.
+pub fn scan_debuts(charts: &[SpotifyChart]) -> Vec<DebutRow> {
+    let mut last_seen: HashMap<(&str, &str), &str> = HashMap::new();
+    let mut rows = Vec::new();
+
+    for (day, chart) in charts.iter().enumerate() {
+        let previous_date = add_days(&chart.date_string, -1).unwrap_or_default();
+        let previous = day
+            .checked_sub(1)
+            .map(|day| &charts[day])
+            .filter(|previous| previous.date_string == previous_date);
+        if day > 0 && previous.is_none() {
+            eprintln!(
+                "{} - Previous day[{}] data missing!",
+                chart.code, previous_date
+            );
+        }
+
+        if let Some(previous) = previous {
+            for entry in &chart.chart {
+                if previous.find_exact(&entry.title, &entry.artist).is_some() {
+                    continue;
+                }
+
+                let seen = last_seen.get(&(entry.title.as_str(), entry.artist.as_str()));
+                rows.push(DebutRow {
+                    date: chart.date_string.clone(),
+                    title: entry.title.clone(),
+                    artist: entry.artist.clone(),
+                    rank: entry.rank,
+                    streams: entry.streams,
+                    kind: if seen.is_some() {
+                        DebutKind::ReEntry
+                    } else {
+                        DebutKind::Debut
+                    },
+                    last_seen: seen.map(|date| date.to_string()),
+                });
+            }
+        }
+
+        for entry in &chart.chart {
+            last_seen.insert(
+                (entry.title.as_str(), entry.artist.as_str()),
+                chart.date_string.as_str(),
+            );
+        }
+    }
+
+    rows
+}
+
+// Debut rows grouped by the credited artist, most debuts first and then by
+// the best debut rank
+pub fn artist_debuts(rows: &[DebutRow]) -> Vec<ArtistDebuts> {
+    let mut artists: BTreeMap<&str, ArtistDebuts> = BTreeMap::new();
+    for row in rows {
+        let artist = artists.entry(&row.artist).or_insert(ArtistDebuts {
+            artist: row.artist.clone(),
+            debuts: 0,
+            best_rank: row.rank,
+            streams: 0,
+        });
+        artist.debuts += 1;
+        artist.best_rank = artist.best_rank.min(row.rank);
+        artist.streams += row.streams;
+    }
+
+    let mut artists: Vec<ArtistDebuts> = artists.into_values().collect();
+    artists.sort_by(|a, b| {
+        b.debuts
+            .cmp(&a.debuts)
+            .then(a.best_rank.cmp(&b.best_rank))
+            .then(b.streams.cmp(&a.streams))
+    });
+    artists
+}
+
+// Debuts and re-entries of a region between two dates. The whole archive up
+// to `to` is scanned so songs that charted before `from` count as re-entries.
+// With `biggest` the rows are ranked by debut rank instead of listed by day,
+// with `by_artist` they are counted per artist. Both only count true debuts
+// unless `kind` asks for re-entries.
+#[allow(clippy::too_many_arguments)]
+pub fn debuts(
+    code: &str,
+    from: Option<&str>,
+    to: Option<&str>,
+    kind: Option<DebutKind>,
+    biggest: bool,
+    by_artist: bool,
+    limit: Option<usize>,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let dates: Vec<String> = available_dates(code)?
+        .into_iter()
+        .filter(|date| to.is_none_or(|to| date.as_str() <= to))
+        .collect();
+    let charts = dates
+        .iter()
+        .map(|date| SpotifyChart::from_code_date(code, date))
+        .collect::<Result<Vec<SpotifyChart>, Box<dyn Error>>>()?;
+
+    let kind = match kind {
+        None if biggest || by_artist => Some(DebutKind::Debut),
+        kind => kind,
+    };
+    let mut rows: Vec<DebutRow> = scan_debuts(&charts)
+        .into_iter()
+        .filter(|row| from.is_none_or(|from| row.date.as_str() >= from))
+        .filter(|row| kind.is_none_or(|kind| row.kind == kind))
+        .collect();
+    if biggest {
+        rows.sort_by(|a, b| a.rank.cmp(&b.rank).then(b.streams.cmp(&a.streams)));
+    } else {
+        rows.sort_by(|a, b| a.date.cmp(&b.date).then(a.rank.cmp(&b.rank)));
+    }
+
+    if by_artist {
+        let mut artists = artist_debuts(&rows);
+        if let Some(limit) = limit {
+            artists.truncate(limit);
+        }
+        if format == Format::Text {
+            println!(
+                "Debuts by artist: code<{}> from<{}> to<{}> - {} artists",
+                code,
+                from.unwrap_or("-"),
+                to.unwrap_or("-"),
+                artists.len()
+            );
+        }
+        return write_report(
+            std::io::stdout(),
+            format,
+            &["Artist", "Debuts", "Best", "Streams"],
+            &artists,
+            |artist| {
+                vec![
+                    artist.artist.clone(),
+                    artist.debuts.to_string(),
+                    artist.best_rank.to_string(),
+                    add_comma(artist.streams),
+                ]
+            },
+        );
+    }
+
+    if let Some(limit) = limit {
+        rows.truncate(limit);
+    }
+    if format == Format::Text {
+        println!(
+            "Debuts: code<{}> from<{}> to<{}> - {} results",
+            code,
+            from.unwrap_or("-"),
+            to.unwrap_or("-"),
+            rows.len()
+        );
+    }
+    write_report(
+        std::io::stdout(),
+        format,
+        &[
+            "Date",
+            "Rank",
+            "Title",
+            "Artist",
+            "Streams",
+            "Kind",
+            "Last seen",
+        ],
+        &rows,
+        |row| {
+            vec![
+                row.date.clone(),
+                row.rank.to_string(),
+                row.title.clone(),
+                row.artist.clone(),
+                add_comma(row.streams),
+                match row.kind {
+                    DebutKind::Debut => "[NE]".to_string(),
+                    DebutKind::ReEntry => "[RE]".to_string(),
+                },
+                row.last_seen.clone().unwrap_or_default(),
+            ]
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spotify::fixtures::{chart, titles};
+
+    #[test]
+    fn debut_and_re_entry() {
+        let charts = vec![
+            chart("us", "2022-06-01", titles(&["A", "B"])),
+            chart("us", "2022-06-02", titles(&["A", "C"])),
+            chart("us", "2022-06-03", titles(&["B", "A", "C"])),
+        ];
+        let rows = scan_debuts(&charts);
+
+        assert_eq!(2, rows.len());
+        assert_eq!(
+            ("C", DebutKind::Debut, 2),
+            (rows[0].title.as_str(), rows[0].kind, rows[0].rank)
+        );
+        assert_eq!(
+            ("B", DebutKind::ReEntry),
+            (rows[1].title.as_str(), rows[1].kind)
+        );
+        assert_eq!(Some("2022-06-01".to_string()), rows[1].last_seen);
+    }
+
+    #[test]
+    fn no_debuts_after_a_missing_day() {
+        let charts = vec![
+            chart("us", "2022-06-01", titles(&["A", "B"])),
+            chart("us", "2022-06-03", titles(&["A", "C"])),
+            chart("us", "2022-06-04", titles(&["A", "C", "D", "B"])),
+        ];
+        let rows = scan_debuts(&charts);
+
+        // C may have entered on the missing 2022-06-02, so only the 4th counts
+        let found: Vec<(&str, &str, DebutKind)> = rows
+            .iter()
+            .map(|row| (row.date.as_str(), row.title.as_str(), row.kind))
+            .collect();
+        assert_eq!(
+            vec![
+                ("2022-06-04", "D", DebutKind::Debut),
+                ("2022-06-04", "B", DebutKind::ReEntry),
+            ],
+            found
+        );
+    }
+
+    #[test]
+    fn debuts_per_artist() {
+        let row = |title: &str, artist: &str, rank: i16, streams: i64| DebutRow {
+            date: "2022-06-02".to_string(),
+            title: title.to_string(),
+            artist: artist.to_string(),
+            rank,
+            streams,
+            kind: DebutKind::Debut,
+            last_seen: None,
+        };
+        let rows = vec![
+            row("A", "Solo", 1, 5000),
+            row("B", "Band", 20, 1000),
+            row("C", "Band", 8, 2000),
+            row("D", "Other", 3, 4000),
+        ];
+
+        assert_eq!(
+            vec![
+                ArtistDebuts {
+                    artist: "Band".to_string(),
+                    debuts: 2,
+                    best_rank: 8,
+                    streams: 3000,
+                },
+                ArtistDebuts {
+                    artist: "Solo".to_string(),
+                    debuts: 1,
+                    best_rank: 1,
+                    streams: 5000,
+                },
+                ArtistDebuts {
+                    artist: "Other".to_string(),
+                    debuts: 1,
+                    best_rank: 3,
+                    streams: 4000,
+                },
+            ],
+            artist_debuts(&rows)
+        );
+    }
+}
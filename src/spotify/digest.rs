@@ -0,0 +1,305 @@
+use std::error::Error;
+
+use serde::Serialize;
+
+use super::{
+    filter::Filter,
+    fmt::{add_comma, add_comma_plus, write_report, Format},
+    history::percent_string,
+    regions::Regions,
+    watchlist::Watchlist,
+    SpotifyChart, SpotifyEntry, SpotifyGain,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    Charting,
+    // On the chart today, not the day before
+    New,
+    // On the chart the day before, not today
+    DropOut,
+    // On the chart today, without a previous day to compare with
+    Unknown,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DigestRow {
+    pub track: String,
+    pub region: String,
+    pub code: String,
+    pub title: String,
+    pub artist: String,
+    pub rank: i16,
+    pub previous_rank: i16,
+    pub streams: i64,
+    // None without a previous day
+    pub streams_diff: Option<i64>,
+    pub percent_diff: f64,
+    pub status: Status,
+}
+
+// Today's and the previous day's entry of every song a filter follows in a
+// region. The previous day is looked up by today's exact match, so a similar
+// title is not mistaken for the same track. With `every_song` all matching
+// songs are followed instead of the first one. Without a previous chart only
+// today's songs are looked up.
+fn lookups<'a>(
+    filter: &Filter,
+    every_song: bool,
+    chart: &'a SpotifyChart,
+    previous_chart: Option<&'a SpotifyChart>,
+) -> Vec<(Option<&'a SpotifyEntry>, Option<&'a SpotifyEntry>)> {
+    if !every_song {
+        let today = chart.find_where(filter);
+        let yesterday = previous_chart.and_then(|previous_chart| match today {
+            Some(entry) => previous_chart.find_exact(&entry.title, &entry.artist),
+            None => previous_chart.find_where(filter),
+        });
+        return vec![(today, yesterday)];
+    }
+
+    let mut pairs: Vec<(Option<&SpotifyEntry>, Option<&SpotifyEntry>)> = chart
+        .find_all_where(filter)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|entry| {
+            let yesterday = previous_chart
+                .and_then(|previous_chart| previous_chart.find_exact(&entry.title, &entry.artist));
+            (Some(entry), yesterday)
+        })
+        .collect();
+    // Songs of the previous day that are gone today
+    if let Some(previous_chart) = previous_chart {
+        pairs.extend(
+            previous_chart
+                .find_all_where(filter)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|entry| chart.find_exact(&entry.title, &entry.artist).is_none())
+                .map(|entry| (None, Some(entry))),
+        );
+    }
+    pairs
+}
+
+// Rows of every watched track in every pair of (today, previous day) charts,
+// in watchlist order and most streamed region first. The songs of a region
+// without its previous day get an unknown status.
+pub fn digest_rows(
+    tracks: &[(String, Filter, bool)],
+    charts: &[(SpotifyChart, Option<SpotifyChart>)],
+) -> Vec<DigestRow> {
+    let mut rows = Vec::new();
+    for (label, filter, every_song) in tracks {
+        let mut track_rows: Vec<DigestRow> = Vec::new();
+        for (chart, previous_chart) in charts {
+            let previous_chart = previous_chart.as_ref();
+            for (today, yesterday) in lookups(filter, *every_song, chart, previous_chart) {
+                let gain = SpotifyGain::from_lookup(today, yesterday, "Unknown", "Unknown");
+                let status = match (gain.today_rank, gain.yesterday_rank) {
+                    (0, 0) => continue,
+                    _ if previous_chart.is_none() => Status::Unknown,
+                    (0, _) => Status::DropOut,
+                    (_, 0) => Status::New,
+                    _ => Status::Charting,
+                };
+                track_rows.push(DigestRow {
+                    // The artist's songs are told apart by their title
+                    track: if *every_song {
+                        format!("{} - {}", gain.title, gain.artist)
+                    } else {
+                        label.clone()
+                    },
+                    region: chart.region.clone(),
+                    code: chart.code.clone(),
+                    title: gain.title,
+                    artist: gain.artist,
+                    rank: gain.today_rank,
+                    previous_rank: gain.yesterday_rank,
+                    streams: gain.today_streams,
+                    streams_diff: previous_chart.map(|_| gain.streams_diff),
+                    percent_diff: gain.percent_diff,
+                    status,
+                });
+            }
+        }
+        track_rows.sort_by_key(|row| std::cmp::Reverse(row.streams));
+        rows.extend(track_rows);
+    }
+    rows
+}
+
+// Digest of a watchlist on a date, every chart is read once for all tracks
+pub fn digest(watchlist: &str, date: &str, format: Format) -> Result<(), Box<dyn Error>> {
+    let watchlist = Watchlist::load(watchlist)?;
+    let tracks = watchlist
+        .tracks
+        .iter()
+        .map(|track| Ok((track.label(), track.filter()?, track.every_song())))
+        .collect::<Result<Vec<(String, Filter, bool)>, Box<dyn Error>>>()?;
+    let codes: Vec<String> = if watchlist.regions.is_empty() {
+        Regions::regions_vec()
+            .into_iter()
+            .map(String::from)
+            .collect()
+    } else {
+        watchlist.regions.clone()
+    };
+
+    let mut charts = Vec::new();
+    for code in &codes {
+        let chart = match SpotifyChart::from_code_date(code, date) {
+            Ok(chart) => chart,
+            Err(_) => {
+                eprintln!("{} - Today[{}] data missing!", code, date);
+                continue;
+            }
+        };
+        let previous_chart = chart.previous_day().ok();
+        if previous_chart.is_none() {
+            eprintln!("{} - Previous day[-] data missing!", code);
+        }
+        charts.push((chart, previous_chart));
+    }
+
+    let rows = digest_rows(&tracks, &charts);
+    if format == Format::Text {
+        let count = |status| rows.iter().filter(|row| row.status == status).count();
+        println!(
+            "Digest: date<{}> tracks<{}> regions<{}> - {} new, {} drop-outs, {} unknown",
+            date,
+            tracks.len(),
+            charts.len(),
+            count(Status::New),
+            count(Status::DropOut),
+            count(Status::Unknown)
+        );
+    }
+    write_report(
+        std::io::stdout(),
+        format,
+        &[
+            "Track", "Region", "Rank", "+/-", "Streams", "Change", "%", "Status",
+        ],
+        &rows,
+        |row| {
+            vec![
+                row.track.clone(),
+                row.region.clone(),
+                if row.rank == 0 {
+                    "-".to_string()
+                } else {
+                    row.rank.to_string()
+                },
+                match row.status {
+                    Status::Charting => format!("{:+}", row.previous_rank - row.rank),
+                    Status::New => "NE".to_string(),
+                    Status::DropOut => format!("was {}", row.previous_rank),
+                    Status::Unknown => "N/A".to_string(),
+                },
+                add_comma(row.streams),
+                row.streams_diff.map_or("N/A".to_string(), add_comma_plus),
+                if row.status == Status::Charting {
+                    percent_string(row.percent_diff)
+                } else {
+                    "N/A".to_string()
+                },
+                match row.status {
+                    Status::Charting => String::new(),
+                    Status::New => "new".to_string(),
+                    Status::DropOut => "drop-out".to_string(),
+                    Status::Unknown => "unknown".to_string(),
+                },
+            ]
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spotify::{
+        filter::Filter,
+        fixtures::{chart, titles},
+    };
+
+    #[test]
+    fn new_and_drop_outs() -> Result<(), Box<dyn Error>> {
+        let tracks = vec![
+            ("A".to_string(), Filter::parse("title:A")?, false),
+            ("B".to_string(), Filter::parse("title:B")?, false),
+        ];
+        let charts = vec![
+            (
+                chart("us", "2022-06-02", titles(&["A", "B"])),
+                Some(chart("us", "2022-06-01", titles(&["B", "A"]))),
+            ),
+            // "Ax" matches title:A as well but is not the same track
+            (
+                chart("ca", "2022-06-02", titles(&["A"])),
+                Some(chart("ca", "2022-06-01", titles(&["Ax"]))),
+            ),
+            (
+                chart("gb", "2022-06-02", titles(&["A"])),
+                Some(chart("gb", "2022-06-01", titles(&["B"]))),
+            ),
+        ];
+        let rows = digest_rows(&tracks, &charts);
+
+        let statuses: Vec<(&str, &str, Status)> = rows
+            .iter()
+            .map(|row| (row.track.as_str(), row.code.as_str(), row.status))
+            .collect();
+        assert_eq!(
+            vec![
+                ("A", "us", Status::Charting),
+                ("A", "ca", Status::New),
+                ("A", "gb", Status::New),
+                ("B", "us", Status::Charting),
+                ("B", "gb", Status::DropOut),
+            ],
+            statuses
+        );
+        assert_eq!(1, rows[0].previous_rank - rows[0].rank);
+        Ok(())
+    }
+
+    #[test]
+    fn every_song_of_an_artist() -> Result<(), Box<dyn Error>> {
+        let tracks = vec![("Artist".to_string(), Filter::parse("artist:Artist")?, true)];
+        let charts = vec![
+            (
+                chart("us", "2022-06-02", titles(&["A", "B", "C"])),
+                Some(chart("us", "2022-06-01", titles(&["B", "D"]))),
+            ),
+            // No previous day: nothing to compare with, no drop-outs
+            (chart("gb", "2022-06-02", titles(&["A"])), None),
+        ];
+        let rows = digest_rows(&tracks, &charts);
+
+        let songs: Vec<(&str, &str, i16, Status)> = rows
+            .iter()
+            .map(|row| {
+                (
+                    row.code.as_str(),
+                    row.title.as_str(),
+                    row.previous_rank,
+                    row.status,
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("us", "A", 0, Status::New),
+                ("gb", "A", 0, Status::Unknown),
+                ("us", "B", 1, Status::Charting),
+                ("us", "C", 0, Status::New),
+                ("us", "D", 2, Status::DropOut),
+            ],
+            songs
+        );
+        assert_eq!(None, rows[1].streams_diff);
+        Ok(())
+    }
+}
@@ -0,0 +1,449 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fs,
+};
+
+use regex::Regex;
+use time::OffsetDateTime;
+
+use super::{
+    add_days, available_codes, available_dates, date_to_string,
+    fmt::{add_comma, escape_html, slug},
+    validate::verify_code,
+    SpotifyChart, SpotifyGain,
+};
+
+// Movers and debuts listed for each region
+const TOP: usize = 5;
+// Namespace of the elements the feed keeps for itself, like the region count
+const NAMESPACE: &str = "tag:chartscan,2022:feed-data";
+
+// Regions that share one entry per date, given as `name=code,code` or just
+// the codes
+#[derive(Debug, PartialEq)]
+pub struct Group {
+    pub name: String,
+    pub codes: Vec<String>,
+}
+
+impl Group {
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let (name, codes) = text.split_once('=').unwrap_or((text, text));
+        let codes: Vec<String> = codes
+            .split(',')
+            .map(|code| code.trim().to_string())
+            .filter(|code| !code.is_empty())
+            .collect();
+        if codes.is_empty() {
+            return Err(From::from(format!(
+                "Region group \"{}\" has no codes",
+                text
+            )));
+        }
+        if let Some(code) = codes.iter().find(|code| !verify_code(code)) {
+            return Err(From::from(format!("Invalid code: \"{}\"", code)));
+        }
+        Ok(Group {
+            name: name.trim().to_string(),
+            codes,
+        })
+    }
+
+    // Part of the entry ids, stays the same as long as the name does
+    pub fn slug(&self) -> String {
+        slug(&self.name)
+    }
+}
+
+// What happened on one region's chart against the previous day
+#[derive(Debug)]
+pub struct RegionNews {
+    pub region: String,
+    pub number_one: SpotifyGain,
+    // The #1 of the previous day when it changed
+    pub dethroned: Option<String>,
+    pub movers: Vec<SpotifyGain>,
+    pub debuts: Vec<SpotifyGain>,
+    // Every debut, not only the listed ones
+    pub debut_count: usize,
+}
+
+impl RegionNews {
+    pub fn from_charts(chart: &SpotifyChart, previous_chart: &SpotifyChart) -> Option<Self> {
+        let gains = chart.gains(previous_chart);
+        let number_one = gains.first()?.clone();
+        let dethroned = previous_chart
+            .chart
+            .first()
+            .filter(|entry| entry.title != number_one.title || entry.artist != number_one.artist)
+            .map(|entry| format!("{} - {}", entry.title, entry.artist));
+
+        let (mut debuts, mut movers): (Vec<SpotifyGain>, Vec<SpotifyGain>) =
+            gains.into_iter().partition(|gain| gain.yesterday_rank == 0);
+        let debut_count = debuts.len();
+        debuts.truncate(TOP);
+        movers.retain(|gain| gain.rank_diff > 0);
+        movers.sort_by_key(|gain| (std::cmp::Reverse(gain.rank_diff), gain.today_rank));
+        movers.truncate(TOP);
+
+        Some(RegionNews {
+            region: chart.region.clone(),
+            number_one,
+            dethroned,
+            movers,
+            debuts,
+            debut_count,
+        })
+    }
+
+    fn html(&self) -> String {
+        let song = |gain: &SpotifyGain| escape_html(&format!("{} - {}", gain.title, gain.artist));
+        let mut html = format!(
+            "<h3>{}</h3>\n<p>#1: {}",
+            escape_html(&self.region),
+            song(&self.number_one)
+        );
+        match &self.dethroned {
+            Some(previous) => {
+                html.push_str(&format!(", new #1 replacing {}", escape_html(previous)))
+            }
+            None => html.push_str(", still #1"),
+        }
+        html.push_str(&format!(
+            " ({} streams)</p>\n",
+            add_comma(self.number_one.today_streams)
+        ));
+
+        if !self.movers.is_empty() {
+            html.push_str("<p>Top movers:</p>\n<ul>\n");
+            for gain in &self.movers {
+                html.push_str(&format!(
+                    "<li>{} &#9650;{} to #{}</li>\n",
+                    song(gain),
+                    gain.rank_diff,
+                    gain.today_rank
+                ));
+            }
+            html.push_str("</ul>\n");
+        }
+        if !self.debuts.is_empty() {
+            html.push_str("<p>Debuts:</p>\n<ul>\n");
+            for gain in &self.debuts {
+                html.push_str(&format!(
+                    "<li>{} at #{}</li>\n",
+                    song(gain),
+                    gain.today_rank
+                ));
+            }
+            html.push_str("</ul>\n");
+        }
+        html
+    }
+}
+
+fn entry_id(date: &str, group: &Group) -> String {
+    format!("tag:chartscan,{}:{}", date, group.slug())
+}
+
+// Atom date-time of a moment in UTC
+fn timestamp(moment: OffsetDateTime) -> String {
+    format!(
+        "{}T{:02}:{:02}:{:02}Z",
+        date_to_string(moment.date()),
+        moment.hour(),
+        moment.minute(),
+        moment.second()
+    )
+}
+
+// One Atom entry of a date and group, None when no region has both days.
+// `updated` is the entry's Atom date-time.
+pub fn entry(date: &str, group: &Group, updated: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let previous = add_days(date, -1)?;
+    let news: Vec<RegionNews> = group
+        .codes
+        .iter()
+        .filter_map(|code| {
+            let chart = SpotifyChart::from_code_date(code, date).ok()?;
+            let previous_chart = SpotifyChart::from_code_date(code, &previous).ok()?;
+            RegionNews::from_charts(&chart, &previous_chart)
+        })
+        .collect();
+    if news.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(entry_xml(date, group, &news, updated)))
+}
+
+pub fn entry_xml(date: &str, group: &Group, news: &[RegionNews], updated: &str) -> String {
+    let new_number_ones = news.iter().filter(|news| news.dethroned.is_some()).count();
+    let debuts: usize = news.iter().map(|news| news.debut_count).sum();
+    let summary = format!(
+        "{} regions, {} new #1, {} debuts",
+        news.len(),
+        new_number_ones,
+        debuts
+    );
+    let content: String = news.iter().map(|news| news.html()).collect();
+
+    format!(
+        "  <entry>
+    <title>{date} - {name}</title>
+    <id>{id}</id>
+    <updated>{updated}</updated>
+    <cs:regions>{regions}</cs:regions>
+    <summary>{summary}</summary>
+    <content type=\"html\">{content}</content>
+  </entry>
+",
+        date = date,
+        name = escape_html(&group.name),
+        id = entry_id(date, group),
+        updated = updated,
+        regions = news.len(),
+        summary = escape_html(&summary),
+        content = escape_html(&content),
+    )
+}
+
+// An entry of a feed written before
+#[derive(Debug, PartialEq)]
+pub struct FeedEntry {
+    pub date: String,
+    pub id: String,
+    pub updated: String,
+    // Regions the entry was made from, 0 when the entry does not say
+    pub regions: usize,
+    pub xml: String,
+}
+
+pub fn existing_entries(xml: &str) -> Vec<FeedEntry> {
+    let entry_re = Regex::new(r"(?s)  <entry>.*?</entry>\n").unwrap();
+    let id_re = Regex::new(r"<id>(tag:chartscan,(\d{4}-\d{2}-\d{2}):[^<]*)</id>").unwrap();
+    let updated_re = Regex::new(r"<updated>([^<]+)</updated>").unwrap();
+    let regions_re = Regex::new(r"<cs:regions>(\d+)</cs:regions>").unwrap();
+    entry_re
+        .find_iter(xml)
+        .filter_map(|entry| {
+            let captures = id_re.captures(entry.as_str())?;
+            let updated = updated_re
+                .captures(entry.as_str())
+                .map_or(format!("{}T00:00:00Z", &captures[2]), |updated| {
+                    updated[1].to_string()
+                });
+            let regions = regions_re
+                .captures(entry.as_str())
+                .and_then(|regions| regions[1].parse().ok())
+                .unwrap_or(0);
+            Some(FeedEntry {
+                date: captures[2].to_string(),
+                id: captures[1].to_string(),
+                updated,
+                regions,
+                xml: entry.as_str().to_string(),
+            })
+        })
+        .collect()
+}
+
+// Write the feed to `out`. Entries of the file written before are kept unless
+// `rebuild` is set, only missing ones and ones that more regions have data
+// for by now are read from the archive. A new entry is dated at the start of
+// its chart day, an entry written again gets the current time so readers
+// pick up the change.
+pub fn feed(
+    out: &str,
+    groups: &[String],
+    max: usize,
+    url: Option<&str>,
+    rebuild: bool,
+) -> Result<(), Box<dyn Error>> {
+    let groups = if groups.is_empty() {
+        vec![Group {
+            name: "All regions".to_string(),
+            codes: available_codes()?,
+        }]
+    } else {
+        groups
+            .iter()
+            .map(|group| Group::parse(group))
+            .collect::<Result<Vec<Group>, Box<dyn Error>>>()?
+    };
+
+    let mut entries = match fs::read_to_string(out) {
+        Ok(xml) if !rebuild => existing_entries(&xml),
+        _ => Vec::new(),
+    };
+    let known: BTreeMap<String, usize> = entries
+        .iter()
+        .map(|entry| (entry.id.clone(), entry.regions))
+        .collect();
+    // A full feed only takes dates from its oldest entry on, older ones would
+    // be dropped again
+    let oldest = if entries.len() >= max {
+        entries.iter().map(|entry| entry.date.clone()).min()
+    } else {
+        None
+    };
+
+    let now = timestamp(OffsetDateTime::now_utc());
+    let mut updated = 0;
+    for group in &groups {
+        // A code without data only leaves its regions out of the entries
+        let available: Vec<BTreeSet<String>> = group
+            .codes
+            .iter()
+            .map(|code| {
+                available_dates(code)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .collect()
+            })
+            .collect();
+        let dates: BTreeSet<&String> = available.iter().flatten().collect();
+        for &date in dates.iter().rev().take(max) {
+            if oldest.as_ref().is_some_and(|oldest| date < oldest) {
+                break;
+            }
+            let id = entry_id(date, group);
+            // Regions that have both days in the archive by now
+            let previous = add_days(date, -1)?;
+            let ready = available
+                .iter()
+                .filter(|dates| dates.contains(date) && dates.contains(&previous))
+                .count();
+            let stale = match known.get(&id) {
+                Some(&regions) if regions >= ready => continue,
+                Some(_) => true,
+                None => false,
+            };
+            let entry_updated = if stale {
+                now.clone()
+            } else {
+                format!("{}T00:00:00Z", date)
+            };
+            if let Some(xml) = entry(date, group, &entry_updated)? {
+                if stale {
+                    entries.retain(|entry| entry.id != id);
+                    updated += 1;
+                }
+                entries.push(FeedEntry {
+                    date: date.clone(),
+                    id,
+                    updated: entry_updated,
+                    regions: ready,
+                    xml,
+                });
+            }
+        }
+    }
+
+    // Newest first, and only as many as asked for
+    entries.sort_by(|a, b| b.date.cmp(&a.date).then_with(|| a.id.cmp(&b.id)));
+    entries.truncate(max);
+    let added = entries
+        .iter()
+        .filter(|entry| !known.contains_key(&entry.id))
+        .count();
+
+    let newest = entries
+        .iter()
+        .map(|entry| entry.updated.clone())
+        .max()
+        .unwrap_or("1970-01-01T00:00:00Z".to_string());
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str(&format!(
+        "<feed xmlns=\"http://www.w3.org/2005/Atom\" xmlns:cs=\"{}\">\n",
+        NAMESPACE
+    ));
+    xml.push_str("  <title>ChartScan daily chart movements</title>\n");
+    xml.push_str("  <id>tag:chartscan,2022:feed</id>\n");
+    xml.push_str(&format!("  <updated>{}</updated>\n", newest));
+    xml.push_str("  <author><name>ChartScan</name></author>\n");
+    if let Some(url) = url {
+        xml.push_str(&format!(
+            "  <link rel=\"self\" href=\"{}\"/>\n",
+            escape_html(url)
+        ));
+    }
+    for entry in &entries {
+        xml.push_str(&entry.xml);
+    }
+    xml.push_str("</feed>\n");
+
+    fs::write(out, xml).map_err(|e| format!("{}: {}", e, out))?;
+    eprintln!(
+        "Feed: {} - {} entries, {} new, {} updated",
+        out,
+        entries.len(),
+        added,
+        updated
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spotify::fixtures::{chart, titles};
+
+    #[test]
+    fn parse_groups() -> Result<(), Box<dyn Error>> {
+        let group = Group::parse("North America=us, ca")?;
+        assert_eq!(vec!["us", "ca"], group.codes);
+        assert_eq!("north-america", group.slug());
+        assert_eq!("gb", Group::parse("gb")?.name);
+        assert!(Group::parse("Europe=").is_err());
+        assert!(Group::parse("xx").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn news_and_entries_round_trip() -> Result<(), Box<dyn Error>> {
+        let news = RegionNews::from_charts(
+            &chart("us", "2022-05-02", titles(&["B", "C", "A", "D"])),
+            &chart("us", "2022-05-01", titles(&["A", "B", "C", "E"])),
+        )
+        .unwrap();
+        assert_eq!(Some("A - Artist".to_string()), news.dethroned);
+        let movers: Vec<&str> = news.movers.iter().map(|gain| gain.title.as_str()).collect();
+        assert_eq!(vec!["B", "C"], movers);
+        assert_eq!("D", news.debuts[0].title);
+
+        let group = Group::parse("us")?;
+        let xml = entry_xml("2022-05-02", &group, &[news], "2022-05-03T08:30:00Z");
+        assert!(xml.contains("new #1 replacing A - Artist"));
+        let entries = existing_entries(&format!("<feed>\n{}</feed>\n", xml));
+        assert_eq!(
+            vec![FeedEntry {
+                date: "2022-05-02".to_string(),
+                id: "tag:chartscan,2022-05-02:us".to_string(),
+                updated: "2022-05-03T08:30:00Z".to_string(),
+                regions: 1,
+                xml,
+            }],
+            entries
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn region_count_not_read_from_summary() {
+        // Written before the count had its own element
+        let xml = "  <entry>
+    <id>tag:chartscan,2022-05-02:us</id>
+    <summary>3 regions, 0 new #1, 0 debuts</summary>
+  </entry>
+";
+        let entries = existing_entries(xml);
+        assert_eq!(0, entries[0].regions);
+        assert_eq!("2022-05-02T00:00:00Z", entries[0].updated);
+    }
+
+    #[test]
+    fn timestamps() {
+        let moment = OffsetDateTime::from_unix_timestamp(1654074303).unwrap();
+        assert_eq!("2022-06-01T09:05:03Z", timestamp(moment));
+    }
+}
@@ -0,0 +1,549 @@
+use std::{error::Error, fmt};
+
+use regex::{Regex, RegexBuilder};
+
+use super::{artists::Role, parse_int, SpotifyEntry};
+
+// Filter expressions used by `--where`, e.g.
+//     artist:"BTS" and not title:/remix/i or rank<=10
+//
+// Precedence from loosest to tightest: `or`, `and`, `not`. Parentheses can be
+// used to group. A bare word or string without a field matches title or artist.
+// `artist:` matches any credited artist, `lead:` only the lead artists.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Title,
+    // Any credited artist, lead or featured
+    Artist,
+    // Lead artists only
+    Lead,
+    Keyword,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumField {
+    Rank,
+    Streams,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cmp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    // Case-insensitive substring, stored lowercased
+    Contains(String),
+    Regex(Regex, String),
+}
+
+#[derive(Debug, Clone)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Match(Field, Pattern),
+    Compare(NumField, Cmp, i64),
+}
+
+impl Pattern {
+    pub fn contains(text: &str) -> Self {
+        Pattern::Contains(text.to_lowercase())
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        match self {
+            Pattern::Contains(needle) => text.to_lowercase().contains(needle),
+            Pattern::Regex(re, _) => re.is_match(text),
+        }
+    }
+}
+
+impl Cmp {
+    fn apply(&self, lhs: i64, rhs: i64) -> bool {
+        match self {
+            Cmp::Lt => lhs < rhs,
+            Cmp::Le => lhs <= rhs,
+            Cmp::Gt => lhs > rhs,
+            Cmp::Ge => lhs >= rhs,
+            Cmp::Eq => lhs == rhs,
+            Cmp::Ne => lhs != rhs,
+        }
+    }
+}
+
+impl Filter {
+    pub fn parse(input: &str) -> Result<Self, Box<dyn Error>> {
+        let tokens = tokenize(input)?;
+        if tokens.is_empty() {
+            return Err(From::from("Empty filter expression!"));
+        }
+
+        let mut parser = Parser { tokens, pos: 0 };
+        let filter = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(From::from(format!(
+                "Unexpected {} in filter: \"{}\"",
+                token, input
+            )));
+        }
+        Ok(filter)
+    }
+
+    // Combine the old title/artist/keyword options into one filter, all of
+    // them have to match. None when nothing is given.
+    pub fn from_parts(
+        title: Option<&str>,
+        artist: Option<&str>,
+        keyword: Option<&str>,
+    ) -> Option<Self> {
+        [
+            title.map(|title| Filter::Match(Field::Title, Pattern::contains(title))),
+            artist.map(|artist| Filter::Match(Field::Artist, Pattern::contains(artist))),
+            keyword.map(|keyword| Filter::Match(Field::Keyword, Pattern::contains(keyword))),
+        ]
+        .into_iter()
+        .flatten()
+        .reduce(Filter::and)
+    }
+
+    // Parse the `--where` expression and AND it with the other search options
+    pub fn from_options(
+        title: Option<&str>,
+        artist: Option<&str>,
+        keyword: Option<&str>,
+        expr: Option<&str>,
+    ) -> Result<Option<Self>, Box<dyn Error>> {
+        let parts = Filter::from_parts(title, artist, keyword);
+        let expr = expr.map(Filter::parse).transpose()?;
+
+        Ok(match (parts, expr) {
+            (Some(parts), Some(expr)) => Some(parts.and(expr)),
+            (parts, expr) => parts.or(expr),
+        })
+    }
+
+    pub fn and(self, other: Filter) -> Self {
+        Filter::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: Filter) -> Self {
+        Filter::Or(Box::new(self), Box::new(other))
+    }
+
+    pub fn matches(&self, entry: &SpotifyEntry) -> bool {
+        match self {
+            Filter::And(lhs, rhs) => lhs.matches(entry) && rhs.matches(entry),
+            Filter::Or(lhs, rhs) => lhs.matches(entry) || rhs.matches(entry),
+            Filter::Not(inner) => !inner.matches(entry),
+            Filter::Match(Field::Title, pattern) => pattern.is_match(&entry.title),
+            Filter::Match(Field::Artist, pattern) => entry
+                .artists
+                .iter()
+                .any(|credit| pattern.is_match(&credit.name)),
+            Filter::Match(Field::Lead, pattern) => entry
+                .artists
+                .iter()
+                .filter(|credit| credit.role == Role::Lead)
+                .any(|credit| pattern.is_match(&credit.name)),
+            Filter::Match(Field::Keyword, pattern) => {
+                pattern.is_match(&entry.title) || pattern.is_match(&entry.artist)
+            }
+            Filter::Compare(NumField::Rank, cmp, value) => cmp.apply(entry.rank as i64, *value),
+            Filter::Compare(NumField::Streams, cmp, value) => cmp.apply(entry.streams, *value),
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Filter::Or(_, _) => 0,
+            Filter::And(_, _) => 1,
+            _ => 2,
+        }
+    }
+
+    fn fmt_child(&self, child: &Filter, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if child.precedence() < self.precedence() {
+            write!(f, "({})", child)
+        } else {
+            write!(f, "{}", child)
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Filter::And(lhs, rhs) => {
+                self.fmt_child(lhs, f)?;
+                write!(f, " and ")?;
+                self.fmt_child(rhs, f)
+            }
+            Filter::Or(lhs, rhs) => {
+                self.fmt_child(lhs, f)?;
+                write!(f, " or ")?;
+                self.fmt_child(rhs, f)
+            }
+            Filter::Not(inner) => {
+                write!(f, "not ")?;
+                self.fmt_child(inner, f)
+            }
+            Filter::Match(field, pattern) => {
+                let field = match field {
+                    Field::Title => "title:",
+                    Field::Artist => "artist:",
+                    Field::Lead => "lead:",
+                    Field::Keyword => "",
+                };
+                match pattern {
+                    Pattern::Contains(text) => write!(f, "{}{:?}", field, text),
+                    Pattern::Regex(re, flags) => write!(f, "{}/{}/{}", field, re.as_str(), flags),
+                }
+            }
+            Filter::Compare(field, cmp, value) => {
+                let field = match field {
+                    NumField::Rank => "rank",
+                    NumField::Streams => "streams",
+                };
+                let cmp = match cmp {
+                    Cmp::Lt => "<",
+                    Cmp::Le => "<=",
+                    Cmp::Gt => ">",
+                    Cmp::Ge => ">=",
+                    Cmp::Eq => "=",
+                    Cmp::Ne => "!=",
+                };
+                write!(f, "{}{}{}", field, cmp, value)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Colon,
+    Op(Cmp),
+    Word(String),
+    Str(String),
+    Regex(String, String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::LParen => write!(f, "\"(\""),
+            Token::RParen => write!(f, "\")\""),
+            Token::Colon => write!(f, "\":\""),
+            Token::Op(_) => write!(f, "comparison operator"),
+            Token::Word(word) => write!(f, "\"{}\"", word),
+            Token::Str(text) => write!(f, "string {:?}", text),
+            Token::Regex(re, _) => write!(f, "regex /{}/", re),
+        }
+    }
+}
+
+fn is_special(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '(' | ')' | ':' | '"' | '<' | '>' | '=' | '!')
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, Box<dyn Error>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RParen);
+            }
+            ':' => {
+                chars.next();
+                tokens.push(Token::Colon);
+            }
+            '<' | '>' | '=' | '!' => {
+                chars.next();
+                let eq = chars.next_if_eq(&'=').is_some();
+                let op = match (c, eq) {
+                    ('<', false) => Cmp::Lt,
+                    ('<', true) => Cmp::Le,
+                    ('>', false) => Cmp::Gt,
+                    ('>', true) => Cmp::Ge,
+                    ('=', _) => Cmp::Eq,
+                    ('!', true) => Cmp::Ne,
+                    _ => return Err(From::from("Expected \"!=\" in filter!")),
+                };
+                tokens.push(Token::Op(op));
+            }
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped) => text.push(escaped),
+                            None => return Err(From::from("Unterminated string in filter!")),
+                        },
+                        Some(c) => text.push(c),
+                        None => return Err(From::from("Unterminated string in filter!")),
+                    }
+                }
+                tokens.push(Token::Str(text));
+            }
+            '/' => {
+                chars.next();
+                let mut re = String::new();
+                loop {
+                    match chars.next() {
+                        Some('/') => break,
+                        // Keep the escape for the regex engine, except for an escaped slash
+                        Some('\\') => match chars.next() {
+                            Some('/') => re.push('/'),
+                            Some(escaped) => {
+                                re.push('\\');
+                                re.push(escaped);
+                            }
+                            None => return Err(From::from("Unterminated regex in filter!")),
+                        },
+                        Some(c) => re.push(c),
+                        None => return Err(From::from("Unterminated regex in filter!")),
+                    }
+                }
+                let mut flags = String::new();
+                while let Some(flag) = chars.next_if(|c| c.is_ascii_alphabetic()) {
+                    flags.push(flag);
+                }
+                tokens.push(Token::Regex(re, flags));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|&c| !is_special(c)) {
+                    word.push(c);
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Filter, Box<dyn Error>> {
+        let mut lhs = self.parse_and()?;
+        while self.eat_keyword("or") {
+            lhs = lhs.or(self.parse_and()?);
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Filter, Box<dyn Error>> {
+        let mut lhs = self.parse_not()?;
+        while self.eat_keyword("and") {
+            lhs = lhs.and(self.parse_not()?);
+        }
+        Ok(lhs)
+    }
+
+    fn parse_not(&mut self) -> Result<Filter, Box<dyn Error>> {
+        if self.eat_keyword("not") {
+            Ok(Filter::Not(Box::new(self.parse_not()?)))
+        } else {
+            self.parse_atom()
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<Filter, Box<dyn Error>> {
+        match self.next() {
+            Some(Token::LParen) => {
+                let inner = self.parse_or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(inner),
+                    _ => Err(From::from("Missing \")\" in filter!")),
+                }
+            }
+            Some(Token::Word(word)) => match self.peek() {
+                Some(Token::Colon) => {
+                    self.pos += 1;
+                    self.parse_field(&word)
+                }
+                Some(&Token::Op(cmp)) => {
+                    self.pos += 1;
+                    self.parse_compare(&word, cmp)
+                }
+                _ => Ok(Filter::Match(Field::Keyword, Pattern::contains(&word))),
+            },
+            Some(Token::Str(text)) => Ok(Filter::Match(Field::Keyword, Pattern::contains(&text))),
+            Some(Token::Regex(re, flags)) => {
+                Ok(Filter::Match(Field::Keyword, build_regex(&re, &flags)?))
+            }
+            Some(token) => Err(From::from(format!("Unexpected {} in filter!", token))),
+            None => Err(From::from("Unexpected end of filter!")),
+        }
+    }
+
+    fn parse_field(&mut self, name: &str) -> Result<Filter, Box<dyn Error>> {
+        let field = match name.to_lowercase().as_str() {
+            "title" | "t" => Field::Title,
+            "artist" | "a" => Field::Artist,
+            "lead" | "l" => Field::Lead,
+            "keyword" | "k" => Field::Keyword,
+            "rank" | "streams" => return self.parse_compare(name, Cmp::Eq),
+            _ => return Err(From::from(format!("Unknown filter field: \"{}\"", name))),
+        };
+
+        match self.next() {
+            Some(Token::Word(text)) | Some(Token::Str(text)) => {
+                Ok(Filter::Match(field, Pattern::contains(&text)))
+            }
+            Some(Token::Regex(re, flags)) => Ok(Filter::Match(field, build_regex(&re, &flags)?)),
+            _ => Err(From::from(format!("Missing value after \"{}:\"", name))),
+        }
+    }
+
+    fn parse_compare(&mut self, name: &str, cmp: Cmp) -> Result<Filter, Box<dyn Error>> {
+        let field = match name.to_lowercase().as_str() {
+            "rank" => NumField::Rank,
+            "streams" => NumField::Streams,
+            _ => {
+                return Err(From::from(format!(
+                    "Only rank and streams can be compared, got: \"{}\"",
+                    name
+                )))
+            }
+        };
+
+        match self.next() {
+            Some(Token::Word(num)) => Ok(Filter::Compare(field, cmp, parse_int(&num)?)),
+            _ => Err(From::from(format!("Missing number after \"{}\"", name))),
+        }
+    }
+}
+
+fn build_regex(re: &str, flags: &str) -> Result<Pattern, Box<dyn Error>> {
+    let mut builder = RegexBuilder::new(re);
+    for flag in flags.chars() {
+        match flag {
+            'i' => builder.case_insensitive(true),
+            _ => return Err(From::from(format!("Unknown regex flag: \"{}\"", flag))),
+        };
+    }
+    Ok(Pattern::Regex(builder.build()?, flags.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type MyResult<T> = Result<T, Box<dyn Error>>;
+
+    #[test]
+    fn parse_precedence() -> MyResult<()> {
+        let filter = Filter::parse(r#"artist:"BTS" and not title:/remix/i or rank<=10"#)?;
+        assert_eq!(
+            filter.to_string(),
+            r#"artist:"bts" and not title:/remix/i or rank<=10"#
+        );
+
+        assert!(filter.matches(&SpotifyEntry::new(50, "Yet To Come", "BTS", 100)));
+        assert!(!filter.matches(&SpotifyEntry::new(50, "Butter - Remix", "BTS", 100)));
+        assert!(filter.matches(&SpotifyEntry::new(3, "As It Was", "Harry Styles", 100)));
+        assert!(!filter.matches(&SpotifyEntry::new(30, "As It Was", "Harry Styles", 100)));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_parentheses() -> MyResult<()> {
+        let filter = Filter::parse("harry and (rank=1 or streams>2,000,000)")?;
+        assert!(filter.matches(&SpotifyEntry::new(1, "As It Was", "Harry Styles", 100)));
+        assert!(filter.matches(&SpotifyEntry::new(
+            5,
+            "Late Night Talking",
+            "Harry Styles",
+            2000001
+        )));
+        assert!(!filter.matches(&SpotifyEntry::new(
+            5,
+            "Late Night Talking",
+            "Harry Styles",
+            2000000
+        )));
+        assert!(!filter.matches(&SpotifyEntry::new(1, "Glimpse of Us", "Joji", 100)));
+        Ok(())
+    }
+
+    #[test]
+    fn from_parts_all() -> MyResult<()> {
+        let filter = Filter::from_parts(Some("as it"), Some("harry"), Some("styles")).unwrap();
+        assert!(filter.matches(&SpotifyEntry::new(1, "As It Was", "Harry Styles", 100)));
+        assert!(!filter.matches(&SpotifyEntry::new(1, "As It Was", "Someone Else", 100)));
+        assert!(Filter::from_parts(None, None, None).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_invalid() -> MyResult<()> {
+        assert!(Filter::parse("").is_err());
+        assert!(Filter::parse("title:").is_err());
+        assert!(Filter::parse("year>2000").is_err());
+        assert!(Filter::parse("(rank<10").is_err());
+        assert!(Filter::parse("artist:\"BTS").is_err());
+        assert!(Filter::parse("title:/remix/x").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn lead_and_featured() -> MyResult<()> {
+        let song = SpotifyEntry::new(
+            4,
+            "Left and Right (feat. Jung Kook of BTS)",
+            "Charlie Puth",
+            100,
+        );
+        assert!(Filter::parse("artist:\"jung kook\"")?.matches(&song));
+        assert!(!Filter::parse("lead:\"jung kook\"")?.matches(&song));
+        assert!(Filter::parse("lead:puth")?.matches(&song));
+        Ok(())
+    }
+}
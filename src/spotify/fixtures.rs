@@ -0,0 +1,76 @@
+// Charts and histories shared by the tests of the report modules
+
+use super::{
+    history::{HistoryPoint, TrackHistory},
+    regions::{RegionString, Regions},
+    SpotifyChart, SpotifyEntry,
+};
+
+// A chart of a region code and date, named like the real chart of the code
+pub fn chart(code: &str, date: &str, entries: Vec<SpotifyEntry>) -> SpotifyChart {
+    let mut chart = SpotifyChart::new();
+    chart.code = code.to_string();
+    chart.region = Regions::from(code).to_region_string();
+    chart.date_string = date.to_string();
+    chart.chart = entries;
+    chart
+}
+
+// Songs by "Artist" ranked in the order given
+pub fn ranked(songs: &[(&str, i64)]) -> Vec<SpotifyEntry> {
+    songs
+        .iter()
+        .enumerate()
+        .map(|(i, &(title, streams))| SpotifyEntry::new(i as i16 + 1, title, "Artist", streams))
+        .collect()
+}
+
+// Titles by "Artist" ranked in the order given, streams fall from 1000
+pub fn titles(titles: &[&str]) -> Vec<SpotifyEntry> {
+    titles
+        .iter()
+        .enumerate()
+        .map(|(i, title)| SpotifyEntry::new(i as i16 + 1, title, "Artist", 1000 - i as i64))
+        .collect()
+}
+
+// Songs "Song 0", "Song 1", ... by "Artist" with the streams given
+pub fn streams(streams: &[i64]) -> Vec<SpotifyEntry> {
+    streams
+        .iter()
+        .enumerate()
+        .map(|(i, &streams)| {
+            SpotifyEntry::new(i as i16 + 1, &format!("Song {}", i), "Artist", streams)
+        })
+        .collect()
+}
+
+// History of As It Was with a (rank, streams) day from 2022-06-01 on
+pub fn history(region: &str, days: &[(i16, i64)]) -> TrackHistory {
+    TrackHistory {
+        title: "As It Was".to_string(),
+        artist: "Harry Styles".to_string(),
+        region: region.to_string(),
+        points: days
+            .iter()
+            .enumerate()
+            .map(|(i, &(rank, streams))| HistoryPoint {
+                date: format!("2022-06-{:02}", i + 1),
+                rank,
+                streams,
+                estimated: false,
+                streams_diff: 0,
+                percent_diff: f64::NAN,
+                moving_average: None,
+                week_over_week: None,
+                weekday_adjusted: None,
+            })
+            .collect(),
+    }
+}
+
+// History at #1 in the US with the daily streams given
+pub fn streams_history(streams: &[i64]) -> TrackHistory {
+    let days: Vec<(i16, i64)> = streams.iter().map(|&streams| (1, streams)).collect();
+    history("US", &days)
+}
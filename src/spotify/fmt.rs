@@ -1,23 +1,144 @@
+use std::{
+    error::Error,
+    ffi::OsStr,
+    io::{IsTerminal, Write},
+};
+
 use num_format::{Locale, ToFormattedString};
+use serde::Serialize;
 
 use super::SpotifyGain;
 
 pub fn style1(gain: &SpotifyGain, region: String) {
-    println!(
-        "{:11} {:<21} {:3} {:3} [{:+4}] {:>10} {:>10} {:>10} {:>+5.2}%",
+    println!("{}", text_row(gain, &region, None));
+}
+
+// Streams and ranks of the days up to a gain, oldest first. None and 0 when
+// the track is not on the chart.
+#[derive(Debug, Default)]
+pub struct Trend {
+    pub streams: Vec<Option<i64>>,
+    pub ranks: Vec<i16>,
+}
+
+// Fixed width row of a gain in the console, with sparklines of the trend
+pub fn text_row(gain: &SpotifyGain, region: &str, trend: Option<&Trend>) -> String {
+    let mut row = format!(
+        "{:11} {:<21} {:3} {:3} {} [{:+4}] {:>10} {:>10} {:>10} {:>+5.2}%",
         region,
         gain.title,
         gain.yesterday_rank,
         gain.today_rank,
+        arrow(gain),
         gain.rank_diff,
         add_comma(gain.today_streams),
         add_comma(gain.yesterday_streams),
         add_comma_plus(gain.streams_diff),
         gain.percent_diff * 100f64
     );
+    if let Some(trend) = trend {
+        let streams: Vec<Option<f64>> = trend
+            .streams
+            .iter()
+            .map(|streams| streams.map(|streams| streams as f64))
+            .collect();
+        // Rank 1 is the highest bar
+        let ranks: Vec<Option<f64>> = trend
+            .ranks
+            .iter()
+            .map(|&rank| (rank > 0).then_some(-(rank as f64)))
+            .collect();
+        row.push_str(&format!(" {} {}", sparkline(&streams), sparkline(&ranks)));
+    }
+    row
+}
+
+// Horizontal bar of the streams, scaled so `max` fills `width` characters
+pub fn bar_row(gain: &SpotifyGain, region: &str, max: i64, width: usize) -> String {
+    format!(
+        "{:11} {:<width$} {:>10} {} {:3}",
+        region,
+        bar(gain.today_streams as f64, max as f64, width),
+        add_comma(gain.today_streams),
+        arrow(gain),
+        gain.today_rank,
+        width = width
+    )
+}
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+// One block per value between the lowest and highest, a space for None
+pub fn sparkline(values: &[Option<f64>]) -> String {
+    let known = values.iter().flatten();
+    let min = known
+        .clone()
+        .fold(f64::INFINITY, |min, &value| min.min(value));
+    let max = known.fold(f64::NEG_INFINITY, |max, &value| max.max(value));
+    values
+        .iter()
+        .map(|value| match value {
+            Some(_) if max <= min => SPARKS[SPARKS.len() / 2],
+            Some(value) => {
+                let level = ((value - min) / (max - min) * (SPARKS.len() - 1) as f64).round();
+                SPARKS[level as usize]
+            }
+            None => ' ',
+        })
+        .collect()
+}
+
+// Full blocks and an eighth block for the rest
+pub fn bar(value: f64, max: f64, width: usize) -> String {
+    const EIGHTHS: [&str; 8] = ["", "▏", "▎", "▍", "▌", "▋", "▊", "▉"];
+    if max <= 0f64 || value <= 0f64 {
+        return String::new();
+    }
+    let eighths = (value.min(max) / max * width as f64 * 8f64).round() as usize;
+    format!("{}{}", "█".repeat(eighths / 8), EIGHTHS[eighths % 8])
+}
+
+// Colors only on a terminal, and never when NO_COLOR is set
+pub fn use_color() -> bool {
+    color_enabled(
+        std::env::var_os("NO_COLOR").as_deref(),
+        std::io::stdout().is_terminal(),
+    )
+}
+
+// An empty NO_COLOR counts as not set
+pub fn color_enabled(no_color: Option<&OsStr>, terminal: bool) -> bool {
+    no_color.is_none_or(|value| value.is_empty()) && terminal
+}
+
+// Rank movement the way style2 shows it, colored on a terminal
+pub fn arrow(gain: &SpotifyGain) -> String {
+    colored_arrow(gain, use_color())
+}
+
+pub fn colored_arrow(gain: &SpotifyGain, color: bool) -> String {
+    let (symbol, code) = if gain.yesterday_rank == 0 {
+        ("●", "34")
+    } else if gain.rank_diff > 0 {
+        ("▲", "32")
+    } else if gain.rank_diff < 0 {
+        ("▼", "31")
+    } else {
+        ("■", "90")
+    };
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", code, symbol)
+    } else {
+        symbol.to_string()
+    }
 }
 
 pub fn style2(gain: &SpotifyGain, region: String) {
+    println!("{}", html_row(gain, &region));
+}
+
+// Table row of a gain in the daily HTML report
+pub fn html_row(gain: &SpotifyGain, region: &str) -> String {
     let new_entry = "<span style=\"color: #4687d7\">●</span>";
     let increase = "<span style=\"color: #83be28\">▲</span>";
     // let decrease = "<span style=\"color: #3e3e40\">▼</span>";"
@@ -25,8 +146,7 @@ pub fn style2(gain: &SpotifyGain, region: String) {
     let same = "<span style=\"color: #3e3e40\">■</span>";
 
     if gain.yesterday_rank == 0 {
-        let str1 = format!("<tr>\n\t<td class=\"first\">{} [NE] {}</td>\n\t<td class=\"center\">{}</td>\n\t<td>{}</td>\n\t<td>N/A</td>\n\t<td>N/A</td>\n</tr>\n",gain.today_rank, new_entry, region, gain.today_streams.to_formatted_string(&Locale::en));
-        return println!("{}", str1);
+        return format!("<tr>\n\t<td class=\"first\">{} [NE] {}</td>\n\t<td class=\"center\">{}</td>\n\t<td>{}</td>\n\t<td>N/A</td>\n\t<td>N/A</td>\n</tr>\n",gain.today_rank, new_entry, region, gain.today_streams.to_formatted_string(&Locale::en));
     }
 
     let symbol = if gain.rank_diff > 0 {
@@ -38,17 +158,14 @@ pub fn style2(gain: &SpotifyGain, region: String) {
     };
 
     if gain.percent_diff > 0.03 {
-        let str1 = get_html_str(
+        get_html_str(
             gain,
             symbol,
-            &region,
+            region,
             Some(" style=\"color:#1abc9c;\"".to_string()),
-        );
-        println!("{}", str1);
+        )
     } else {
-        let str1 = get_html_str(gain, symbol, &region, None);
-
-        println!("{}", str1);
+        get_html_str(gain, symbol, region, None)
     }
 }
 
@@ -75,14 +192,179 @@ fn get_html_str(gain: &SpotifyGain, symbol: &str, region: &str, style: Option<St
     )
 }
 
-fn add_comma(num: i64) -> String {
-    format!("{}", num.to_formatted_string(&Locale::en))
+pub(super) fn add_comma(num: i64) -> String {
+    num.to_formatted_string(&Locale::en)
 }
 
-fn add_comma_plus(num: i64) -> String {
+pub(super) fn add_comma_plus(num: i64) -> String {
     format!(
         "{}{}",
         if num >= 0 { "+" } else { "" },
         num.to_formatted_string(&Locale::en)
     )
 }
+
+pub(super) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Lowercase words joined by dashes, for ids and file names
+pub(super) fn slug(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-")
+}
+
+// How `daily` prints its rows
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum DailyStyle {
+    // Table rows for the HTML report
+    Html,
+    // Fixed width rows, with sparklines when asked for
+    Text,
+    // Streams of every region as bars
+    Bars,
+}
+
+// Output formats for the table like reports
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    Text,
+    Csv,
+    Json,
+    Html,
+}
+
+// Write rows of a report. JSON serializes the rows as they are, the other
+// formats lay out the strings returned by `to_row` under `header`.
+pub(super) fn write_report<W: Write, T: Serialize>(
+    mut w: W,
+    format: Format,
+    header: &[&str],
+    rows: &[T],
+    to_row: impl Fn(&T) -> Vec<String>,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        Format::Text => {
+            let cells: Vec<Vec<String>> = rows.iter().map(to_row).collect();
+            let widths: Vec<usize> = header
+                .iter()
+                .enumerate()
+                .map(|(i, name)| {
+                    cells
+                        .iter()
+                        .map(|row| row[i].chars().count())
+                        .chain(std::iter::once(name.len()))
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect();
+
+            // Columns of numbers are right aligned, everything else (dates
+            // included) to the left
+            let is_number = |cell: &str| {
+                cell.starts_with(|c: char| c.is_ascii_digit() || "+-~#".contains(c))
+                    && !super::validate::verify_date(cell)
+            };
+            let numeric: Vec<bool> = (0..header.len())
+                .map(|i| {
+                    !cells.is_empty()
+                        && cells
+                            .iter()
+                            .all(|row| row[i].is_empty() || is_number(&row[i]))
+                })
+                .collect();
+
+            let line = |row: Vec<String>| {
+                row.iter()
+                    .zip(&widths)
+                    .zip(&numeric)
+                    .map(|((cell, &width), &numeric)| {
+                        if numeric {
+                            format!("{:>width$}", cell, width = width)
+                        } else {
+                            format!("{:<width$}", cell, width = width)
+                        }
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" ")
+                    .trim_end()
+                    .to_string()
+            };
+
+            writeln!(
+                w,
+                "{}",
+                line(header.iter().map(|h| h.to_string()).collect())
+            )?;
+            for row in cells {
+                writeln!(w, "{}", line(row))?;
+            }
+        }
+        Format::Csv => {
+            let mut csv_wtr = csv::Writer::from_writer(w);
+            csv_wtr.write_record(header)?;
+            for row in rows {
+                csv_wtr.write_record(to_row(row))?;
+            }
+            csv_wtr.flush()?;
+        }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut w, rows)?;
+            writeln!(w)?;
+        }
+        Format::Html => {
+            writeln!(w, "<table>\n<tr>")?;
+            for name in header {
+                writeln!(w, "\t<th>{}</th>", escape_html(name))?;
+            }
+            writeln!(w, "</tr>")?;
+            for row in rows {
+                writeln!(w, "<tr>")?;
+                for (i, cell) in to_row(row).iter().enumerate() {
+                    let class = if i == 0 { " class=\"first\"" } else { "" };
+                    writeln!(w, "\t<td{}>{}</td>", class, escape_html(cell))?;
+                }
+                writeln!(w, "</tr>")?;
+            }
+            writeln!(w, "</table>")?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sparklines_and_bars() {
+        assert_eq!(
+            "▁▅█ ",
+            sparkline(&[Some(1f64), Some(5f64), Some(9f64), None])
+        );
+        assert_eq!("▅▅", sparkline(&[Some(3f64), Some(3f64)]));
+        assert_eq!("", sparkline(&[]));
+        assert_eq!("█████", bar(10f64, 10f64, 5));
+        assert_eq!("██▌", bar(5f64, 10f64, 5));
+        assert_eq!("", bar(0f64, 10f64, 5));
+    }
+
+    #[test]
+    fn no_color() {
+        assert!(color_enabled(None, true));
+        assert!(color_enabled(Some(OsStr::new("")), true));
+        assert!(!color_enabled(Some(OsStr::new("1")), true));
+        assert!(!color_enabled(None, false));
+
+        let gain = SpotifyGain::new(3, 5, "A", "Artist", 100, 90);
+        assert_eq!("▲", colored_arrow(&gain, false));
+        assert_eq!("\x1b[32m▲\x1b[0m", colored_arrow(&gain, true));
+    }
+}
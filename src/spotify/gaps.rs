@@ -0,0 +1,177 @@
+use std::{collections::HashSet, error::Error, fs::File};
+
+use serde::Serialize;
+
+use super::{
+    add_days, available_codes, available_dates, date_range,
+    fmt::{write_report, Format},
+    validate::verify_code,
+};
+
+// Consecutive missing dates, `from` and `to` included
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DateRange {
+    pub from: String,
+    pub to: String,
+    pub days: usize,
+}
+
+impl std::fmt::Display for DateRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.days == 1 {
+            write!(f, "{}", self.from)
+        } else {
+            write!(f, "{}..{}", self.from, self.to)
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct GapReport {
+    pub code: String,
+    pub missing: Vec<String>,
+    pub ranges: Vec<DateRange>,
+}
+
+// Collapse sorted dates into ranges of consecutive days
+pub fn compact_ranges(dates: &[String]) -> Result<Vec<DateRange>, Box<dyn Error>> {
+    let mut ranges: Vec<DateRange> = Vec::new();
+    for date in dates {
+        match ranges.last_mut() {
+            Some(range) if add_days(&range.to, 1)? == *date => {
+                range.to = date.clone();
+                range.days += 1;
+            }
+            _ => ranges.push(DateRange {
+                from: date.clone(),
+                to: date.clone(),
+                days: 1,
+            }),
+        }
+    }
+    Ok(ranges)
+}
+
+// Missing dates of every region directory (or only `code`) between two dates.
+// Without a range the first and last date found in any of the regions is used.
+// The backfill plan is written as `code,date` lines, one per missing chart.
+pub fn gaps(
+    code: Option<&str>,
+    from: Option<&str>,
+    to: Option<&str>,
+    plan: Option<&str>,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let codes = match code {
+        Some(code) if !verify_code(code) => {
+            return Err(From::from(format!("Invalid code: \"{}\"", code)))
+        }
+        Some(code) => vec![code.to_string()],
+        None => available_codes()?,
+    };
+    let available: Vec<(String, Vec<String>)> = codes
+        .into_iter()
+        .map(|code| available_dates(&code).map(|dates| (code, dates)))
+        .collect::<Result<_, _>>()?;
+
+    let all_dates = available.iter().flat_map(|(_, dates)| dates);
+    let from = match from {
+        Some(from) => from.to_string(),
+        None => all_dates
+            .clone()
+            .min()
+            .ok_or("No chart data found!")?
+            .clone(),
+    };
+    let to = match to {
+        Some(to) => to.to_string(),
+        None => all_dates.max().ok_or("No chart data found!")?.clone(),
+    };
+    let range = date_range(&from, &to)?;
+
+    let mut reports = Vec::new();
+    for (code, dates) in available {
+        let dates: HashSet<String> = dates.into_iter().collect();
+        let missing: Vec<String> = range
+            .iter()
+            .filter(|date| !dates.contains(*date))
+            .cloned()
+            .collect();
+        reports.push(GapReport {
+            ranges: compact_ranges(&missing)?,
+            code,
+            missing,
+        });
+    }
+
+    if let Some(plan) = plan {
+        let mut csv_wtr = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(File::create(plan).map_err(|e| format!("{}: {}", e, plan))?);
+        let mut count = 0;
+        for report in &reports {
+            for date in &report.missing {
+                csv_wtr.write_record([&report.code, date])?;
+                count += 1;
+            }
+        }
+        csv_wtr.flush()?;
+        eprintln!("Wrote {} dates to backfill to {}", count, plan);
+    }
+
+    if format == Format::Text {
+        println!(
+            "Gaps: from<{}> to<{}> days<{}> - {} missing",
+            from,
+            to,
+            range.len(),
+            reports
+                .iter()
+                .map(|report| report.missing.len())
+                .sum::<usize>()
+        );
+    }
+    write_report(
+        std::io::stdout(),
+        format,
+        &["Code", "Missing", "Ranges"],
+        &reports,
+        |report| {
+            vec![
+                report.code.clone(),
+                report.missing.len().to_string(),
+                report
+                    .ranges
+                    .iter()
+                    .map(|range| range.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", "),
+            ]
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reject_bad_code() {
+        assert!(gaps(Some("../x"), None, None, None, Format::Text).is_err());
+    }
+
+    #[test]
+    fn ranges_across_months() -> Result<(), Box<dyn Error>> {
+        let dates: Vec<String> = ["2022-05-30", "2022-05-31", "2022-06-01", "2022-06-03"]
+            .iter()
+            .map(|date| date.to_string())
+            .collect();
+        let ranges = compact_ranges(&dates)?;
+
+        assert_eq!(2, ranges.len());
+        assert_eq!("2022-05-30..2022-06-01", ranges[0].to_string());
+        assert_eq!(3, ranges[0].days);
+        assert_eq!("2022-06-03", ranges[1].to_string());
+        Ok(())
+    }
+}
@@ -0,0 +1,556 @@
+use std::{collections::HashMap, error::Error};
+
+use serde::Serialize;
+use time::{Date, Duration};
+
+use super::{
+    filter::Filter,
+    fmt::{add_comma, add_comma_plus, write_report, Format},
+    svg::{history_svg, SvgOptions},
+    validate::{match_date, verify_code},
+    SpotifyChart, SpotifyGain,
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryPoint {
+    pub date: String,
+    // 0 when the track is not on the chart
+    pub rank: i16,
+    pub streams: i64,
+    // Streams are an estimate from the chart's rank curve, not chart data
+    pub estimated: bool,
+    pub streams_diff: i64,
+    pub percent_diff: f64,
+    // Smoothed values, only filled in when asked for. They are computed from
+    // days on the chart only, None when a day they need is missing, off the
+    // chart or only estimated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub moving_average: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub week_over_week: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weekday_adjusted: Option<f64>,
+}
+
+// Which smoothed values are added to a history
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Smoothing {
+    pub moving_average: bool,
+    pub week_over_week: bool,
+    pub weekday_adjusted: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackHistory {
+    pub title: String,
+    pub artist: String,
+    pub region: String,
+    pub points: Vec<HistoryPoint>,
+}
+
+impl TrackHistory {
+    // The first entry matching the filter decides which track is followed,
+    // every chart after that is searched for the exact title and artist.
+    // With `estimate`, off-chart days after the first charting day get an
+    // upper-bound estimate from the day's rank curve instead of 0 streams.
+    pub fn from_charts(charts: &[SpotifyChart], filter: &Filter, estimate: bool) -> Option<Self> {
+        let first = charts.iter().find_map(|chart| chart.find_where(filter))?;
+        Some(TrackHistory::from_charts_exact(
+            charts,
+            &first.title.clone(),
+            &first.artist.clone(),
+            estimate,
+        ))
+    }
+
+    // History of an exact title and artist, also when it is on none of the charts
+    pub fn from_charts_exact(
+        charts: &[SpotifyChart],
+        title: &str,
+        artist: &str,
+        estimate: bool,
+    ) -> Self {
+        let (title, artist) = (title.to_string(), artist.to_string());
+        let mut points: Vec<HistoryPoint> = Vec::new();
+        // Nothing is estimated before the track first charts, it may not even
+        // be out yet
+        let mut charted = false;
+        for chart in charts {
+            let (rank, streams, estimated) = match chart.find_exact(&title, &artist) {
+                Some(entry) => {
+                    charted = true;
+                    (entry.rank, entry.streams, false)
+                }
+                None if estimate && charted => match chart.rank_curve() {
+                    Some(curve) => (0, curve.off_chart_estimate(), true),
+                    None => (0, 0, false),
+                },
+                None => (0, 0, false),
+            };
+
+            let (streams_diff, percent_diff) = match points.last() {
+                Some(previous) => {
+                    let gain = SpotifyGain::new(
+                        rank,
+                        previous.rank,
+                        &title,
+                        &artist,
+                        streams,
+                        previous.streams,
+                    );
+                    (gain.streams_diff, gain.percent_diff)
+                }
+                None => (0, f64::NAN),
+            };
+
+            points.push(HistoryPoint {
+                date: chart.date_string.clone(),
+                rank,
+                streams,
+                estimated,
+                streams_diff,
+                percent_diff,
+                moving_average: None,
+                week_over_week: None,
+                weekday_adjusted: None,
+            });
+        }
+
+        TrackHistory {
+            title,
+            artist,
+            region: charts
+                .first()
+                .map_or(String::new(), |chart| chart.region.clone()),
+            points,
+        }
+    }
+
+    fn dates(&self) -> Vec<Option<Date>> {
+        self.points
+            .iter()
+            .map(|point| match_date(&point.date).ok().flatten())
+            .collect()
+    }
+
+    // Streams of the days on the chart, off-chart and estimated days are left
+    // out so they are not mistaken for real data
+    fn streams_by_date(&self, dates: &[Option<Date>]) -> HashMap<Date, i64> {
+        dates
+            .iter()
+            .zip(&self.points)
+            .filter(|(_, point)| point.rank > 0 && !point.estimated)
+            .filter_map(|(date, point)| Some(((*date)?, point.streams)))
+            .collect()
+    }
+
+    // Streams of the 7 days up to and including the date, only when all of
+    // them are there
+    fn week(by_date: &HashMap<Date, i64>, date: Date) -> Option<Vec<i64>> {
+        (0..7)
+            .map(|offset| by_date.get(&(date - Duration::days(offset))).copied())
+            .collect()
+    }
+
+    // Trailing 7-day average
+    pub fn add_moving_average(&mut self) {
+        let dates = self.dates();
+        let by_date = self.streams_by_date(&dates);
+        for (point, date) in self.points.iter_mut().zip(dates) {
+            point.moving_average = date
+                .and_then(|date| TrackHistory::week(&by_date, date))
+                .map(|week| week.iter().sum::<i64>() as f64 / 7f64);
+        }
+    }
+
+    // Change against the same weekday a week before
+    pub fn add_week_over_week(&mut self) {
+        let dates = self.dates();
+        let by_date = self.streams_by_date(&dates);
+        for (point, date) in self.points.iter_mut().zip(dates) {
+            point.week_over_week = date
+                .and_then(|date| {
+                    Some((
+                        by_date.get(&date)?,
+                        by_date.get(&(date - Duration::days(7)))?,
+                    ))
+                })
+                .filter(|&(_, &previous)| previous > 0)
+                .map(|(&today, &previous)| (today - previous) as f64 / previous as f64);
+        }
+    }
+
+    // Day over day change after taking out the weekday effect. Every weekday
+    // gets a factor, its average streams against the 7-day average, and both
+    // days are divided by their factor before comparing.
+    pub fn add_weekday_adjusted(&mut self) {
+        let dates = self.dates();
+        let by_date = self.streams_by_date(&dates);
+        let weekday = |date: Date| date.weekday().number_days_from_monday() as usize;
+
+        let mut ratios: [Vec<f64>; 7] = Default::default();
+        for date in dates.iter().flatten() {
+            if let Some(week) = TrackHistory::week(&by_date, *date) {
+                let average = week.iter().sum::<i64>() as f64 / 7f64;
+                if average > 0f64 {
+                    ratios[weekday(*date)].push(week[0] as f64 / average);
+                }
+            }
+        }
+        let factors: Vec<Option<f64>> = ratios
+            .iter()
+            .map(|ratios| {
+                if ratios.is_empty() {
+                    None
+                } else {
+                    Some(ratios.iter().sum::<f64>() / ratios.len() as f64)
+                }
+            })
+            .collect();
+
+        for (point, date) in self.points.iter_mut().zip(dates) {
+            let date = match date {
+                Some(date) => date,
+                None => continue,
+            };
+            let yesterday = date - Duration::days(1);
+            point.weekday_adjusted = match (
+                by_date.get(&date),
+                by_date.get(&yesterday),
+                factors[weekday(date)],
+                factors[weekday(yesterday)],
+            ) {
+                (Some(&today), Some(&previous), Some(today_factor), Some(previous_factor))
+                    if previous > 0 && today_factor > 0f64 =>
+                {
+                    let today = today as f64 / today_factor;
+                    let previous = previous as f64 / previous_factor;
+                    Some((today - previous) / previous)
+                }
+                _ => None,
+            };
+        }
+    }
+
+    pub fn smooth(&mut self, smoothing: Smoothing) {
+        if smoothing.moving_average {
+            self.add_moving_average();
+        }
+        if smoothing.week_over_week {
+            self.add_week_over_week();
+        }
+        if smoothing.weekday_adjusted {
+            self.add_weekday_adjusted();
+        }
+    }
+
+    pub fn total_streams(&self) -> i64 {
+        self.points.iter().map(|point| point.streams).sum()
+    }
+
+    pub fn estimated_streams(&self) -> i64 {
+        self.points
+            .iter()
+            .filter(|point| point.estimated)
+            .map(|point| point.streams)
+            .sum()
+    }
+}
+
+pub(super) fn percent_string(percent: f64) -> String {
+    if percent.is_finite() {
+        format!("{:+.2}%", percent * 100f64)
+    } else {
+        "N/A".to_string()
+    }
+}
+
+// Raw numbers so the file can be loaded without parsing commas. Estimates are
+// flagged in their own column, off-chart ranks and missing values are empty.
+fn write_csv<W: std::io::Write>(
+    w: W,
+    history: &TrackHistory,
+    smoothing: Smoothing,
+) -> Result<(), Box<dyn Error>> {
+    let number = |value: Option<f64>| {
+        value
+            .filter(|value| value.is_finite())
+            .map_or(String::new(), |value| value.to_string())
+    };
+    let percent = |value: Option<f64>| number(value.map(|value| value * 100f64));
+
+    let mut csv_wtr = csv::Writer::from_writer(w);
+    let mut header = vec!["Date", "Rank", "Streams", "Estimated", "Change", "%"];
+    if smoothing.moving_average {
+        header.push("7d avg");
+    }
+    if smoothing.week_over_week {
+        header.push("WoW");
+    }
+    if smoothing.weekday_adjusted {
+        header.push("Adj %");
+    }
+    csv_wtr.write_record(&header)?;
+
+    for point in &history.points {
+        let mut record = vec![
+            point.date.clone(),
+            if point.rank == 0 {
+                String::new()
+            } else {
+                point.rank.to_string()
+            },
+            point.streams.to_string(),
+            point.estimated.to_string(),
+            point.streams_diff.to_string(),
+            percent(Some(point.percent_diff)),
+        ];
+        if smoothing.moving_average {
+            record.push(number(point.moving_average));
+        }
+        if smoothing.week_over_week {
+            record.push(percent(point.week_over_week));
+        }
+        if smoothing.weekday_adjusted {
+            record.push(percent(point.weekday_adjusted));
+        }
+        csv_wtr.write_record(&record)?;
+    }
+    csv_wtr.flush()?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn history(
+    code: &str,
+    from: &str,
+    to: &str,
+    filter: &Filter,
+    estimate: bool,
+    smoothing: Smoothing,
+    svg: &SvgOptions,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(other_code) = svg.compare_codes.iter().find(|code| !verify_code(code)) {
+        return Err(From::from(format!("Invalid code: \"{}\"", other_code)));
+    }
+    let charts = SpotifyChart::from_code_range(code, from, to)?;
+    let mut history = TrackHistory::from_charts(&charts, filter, estimate).ok_or(format!(
+        "No track matching where<{}> in {} from {} to {}",
+        filter, code, from, to
+    ))?;
+    history.smooth(smoothing);
+
+    // The chart is drawn for `--svg` and the HTML report
+    let image = if svg.out.is_some() || format == Format::Html {
+        let mut histories = vec![history.clone()];
+        for other in &svg.compare {
+            match TrackHistory::from_charts(&charts, other, estimate) {
+                Some(other) => histories.push(other),
+                None => eprintln!("No track matching where<{}> in {}", other, code),
+            }
+        }
+        for other_code in &svg.compare_codes {
+            let charts = SpotifyChart::from_code_range(other_code, from, to)?;
+            let other =
+                TrackHistory::from_charts_exact(&charts, &history.title, &history.artist, estimate);
+            // A region the song never charted in would only add a legend line
+            if other.points.iter().all(|point| point.rank == 0) {
+                eprintln!(
+                    "\"{}\" by {} not on the {} chart from {} to {}",
+                    history.title, history.artist, other_code, from, to
+                );
+                continue;
+            }
+            histories.push(other);
+        }
+        Some(history_svg(&histories, svg.bars)?)
+    } else {
+        None
+    };
+    if let (Some(out), Some(image)) = (&svg.out, &image) {
+        std::fs::write(out, image).map_err(|e| format!("{}: {}", e, out))?;
+    }
+
+    if format == Format::Json {
+        serde_json::to_writer_pretty(std::io::stdout(), &history)?;
+        println!();
+        return Ok(());
+    }
+
+    if format == Format::Csv {
+        return write_csv(std::io::stdout(), &history, smoothing);
+    }
+
+    if format == Format::Text {
+        println!(
+            "History: title<\"{}\"> artist<\"{}\"> code<{}>",
+            history.title, history.artist, code
+        );
+    }
+    if let (Format::Html, Some(image)) = (format, &image) {
+        print!("{}", image);
+    }
+    let mut header = vec!["Date", "Rank", "Streams", "Change", "%"];
+    if smoothing.moving_average {
+        header.push("7d avg");
+    }
+    if smoothing.week_over_week {
+        header.push("WoW");
+    }
+    if smoothing.weekday_adjusted {
+        header.push("Adj %");
+    }
+
+    write_report(
+        std::io::stdout(),
+        format,
+        &header,
+        &history.points,
+        |point| {
+            let mut cells = vec![
+                point.date.clone(),
+                if point.rank == 0 {
+                    "-".to_string()
+                } else {
+                    point.rank.to_string()
+                },
+                // Estimates are marked so they are not mistaken for chart data
+                if point.estimated {
+                    format!("~{}", add_comma(point.streams))
+                } else {
+                    add_comma(point.streams)
+                },
+                add_comma_plus(point.streams_diff),
+                percent_string(point.percent_diff),
+            ];
+            if smoothing.moving_average {
+                cells.push(
+                    point
+                        .moving_average
+                        .map(|average| add_comma(average.round() as i64))
+                        .unwrap_or_else(|| "-".to_string()),
+                );
+            }
+            if smoothing.week_over_week {
+                cells.push(percent_string(point.week_over_week.unwrap_or(f64::NAN)));
+            }
+            if smoothing.weekday_adjusted {
+                cells.push(percent_string(point.weekday_adjusted.unwrap_or(f64::NAN)));
+            }
+            cells
+        },
+    )?;
+    if format == Format::Text {
+        println!(
+            "Total: {} streams ({} estimated)",
+            add_comma(history.total_streams()),
+            add_comma(history.estimated_streams())
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spotify::fixtures::{self, chart, streams_history as history, titles};
+
+    #[test]
+    fn moving_average_full_week() {
+        let mut history = history(&[70, 70, 70, 70, 70, 70, 140, 140]);
+        history.add_moving_average();
+
+        assert_eq!(None, history.points[5].moving_average);
+        assert_eq!(Some(80f64), history.points[6].moving_average);
+        assert_eq!(Some(90f64), history.points[7].moving_average);
+    }
+
+    #[test]
+    fn week_over_week_same_weekday() {
+        let mut history = history(&[100, 1, 1, 1, 1, 1, 1, 150]);
+        history.add_week_over_week();
+
+        assert_eq!(None, history.points[6].week_over_week);
+        assert_eq!(Some(0.5), history.points[7].week_over_week);
+    }
+
+    #[test]
+    fn weekday_adjusted_flat() {
+        // Same weekly pattern every week: no change once the weekday effect is out
+        let week = [100, 100, 100, 100, 200, 150, 100];
+        let streams: Vec<i64> = week.iter().cycle().take(21).copied().collect();
+        let mut history = history(&streams);
+        history.add_weekday_adjusted();
+
+        let change = history.points[18].weekday_adjusted.unwrap();
+        assert!(change.abs() < 1e-9);
+    }
+
+    #[test]
+    fn estimate_only_after_first_charting_day() {
+        let charts = [
+            chart("us", "2022-06-01", titles(&["A", "B", "C"])),
+            chart("us", "2022-06-02", titles(&["A", "Song", "C"])),
+            chart("us", "2022-06-03", titles(&["A", "B", "C"])),
+        ];
+        let history = TrackHistory::from_charts_exact(&charts, "Song", "Artist", true);
+
+        // A leading gap stays at 0, a gap after charting is estimated
+        assert_eq!((0, 0, false), point(&history, 0));
+        assert_eq!((2, 999, false), point(&history, 1));
+        let (rank, streams, estimated) = point(&history, 2);
+        assert_eq!((0, true), (rank, estimated));
+        assert!(streams > 0 && streams <= 998);
+    }
+
+    #[test]
+    fn trends_leave_out_off_chart_days() {
+        let mut days = vec![(1, 100); 9];
+        days[3] = (0, 0);
+        let mut history = fixtures::history("US", &days);
+        history.points[8].estimated = true;
+        history.smooth(Smoothing {
+            moving_average: true,
+            week_over_week: true,
+            weekday_adjusted: true,
+        });
+
+        // Every week up to day 9 has the off-chart day 4 in it, and day 9
+        // itself is an estimate
+        assert!(history
+            .points
+            .iter()
+            .all(|point| point.moving_average.is_none()));
+        assert_eq!(Some(0f64), history.points[7].week_over_week);
+        assert_eq!(None, history.points[8].week_over_week);
+        assert!(history
+            .points
+            .iter()
+            .all(|point| point.weekday_adjusted.is_none()));
+    }
+
+    #[test]
+    fn csv_raw_numbers() -> Result<(), Box<dyn Error>> {
+        let mut history = fixtures::history("US", &[(3, 1000), (0, 250)]);
+        history.points[1].estimated = true;
+        history.points[1].streams_diff = -750;
+        history.points[1].percent_diff = -0.75;
+
+        let mut out = Vec::new();
+        write_csv(&mut out, &history, Smoothing::default())?;
+        assert_eq!(
+            "Date,Rank,Streams,Estimated,Change,%\n\
+             2022-06-01,3,1000,false,0,\n\
+             2022-06-02,,250,true,-750,-75\n",
+            String::from_utf8(out)?
+        );
+        Ok(())
+    }
+
+    fn point(history: &TrackHistory, i: usize) -> (i16, i64, bool) {
+        let point = &history.points[i];
+        (point.rank, point.streams, point.estimated)
+    }
+}
@@ -0,0 +1,203 @@
+use std::{collections::HashSet, error::Error, fs::File, io::Read};
+
+use serde::Serialize;
+
+use super::{
+    available_codes, available_dates, data_dir,
+    fmt::{write_report, Format},
+    parse_int,
+};
+
+// Rows the downloader expects on every chart
+pub const EXPECTED_ROWS: usize = 200;
+
+#[derive(Debug, Serialize)]
+pub struct FileReport {
+    pub code: String,
+    pub date: String,
+    pub rows: usize,
+    pub problems: Vec<String>,
+}
+
+impl FileReport {
+    pub fn ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+// Problems in the `*` delimited records of a chart file: the row count, ranks
+// running 1..N, streams never going up with rank, no title/artist pair twice
+// and numbers that parse. Returns the number of rows read as well.
+pub fn check_records<R: Read>(reader: R, expected_rows: usize) -> (usize, Vec<String>) {
+    let mut csv_rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(b'*')
+        .from_reader(reader);
+
+    let mut problems = Vec::new();
+    let mut seen: HashSet<(String, String)> = HashSet::new();
+    let mut previous_rank = 0;
+    let mut previous_streams: Option<i64> = None;
+    let mut rows = 0;
+
+    for (i, rec) in csv_rdr.records().enumerate() {
+        let line = i + 1;
+        rows += 1;
+        let rec = match rec {
+            Ok(rec) => rec,
+            Err(e) => {
+                problems.push(format!("line {}: {}", line, e));
+                continue;
+            }
+        };
+        if rec.len() != 4 {
+            problems.push(format!("line {}: {} fields, expected 4", line, rec.len()));
+            continue;
+        }
+
+        // Compared to the rank before so a gap is only reported once
+        match rec[0].parse::<usize>() {
+            Ok(rank) => {
+                if rank != previous_rank + 1 {
+                    problems.push(format!(
+                        "line {}: rank {}, expected {}",
+                        line,
+                        rank,
+                        previous_rank + 1
+                    ));
+                }
+                previous_rank = rank;
+            }
+            Err(_) => {
+                problems.push(format!("line {}: invalid rank \"{}\"", line, &rec[0]));
+                previous_rank += 1;
+            }
+        }
+
+        match parse_int(&rec[3]) {
+            Ok(streams) => {
+                if let Some(previous) = previous_streams.filter(|&previous| streams > previous) {
+                    problems.push(format!(
+                        "line {}: streams {} above the rank before ({})",
+                        line, streams, previous
+                    ));
+                }
+                previous_streams = Some(streams);
+            }
+            Err(_) => problems.push(format!("line {}: invalid streams \"{}\"", line, &rec[3])),
+        }
+
+        if !seen.insert((rec[1].to_string(), rec[2].to_string())) {
+            problems.push(format!(
+                "line {}: duplicate \"{}\" by \"{}\"",
+                line, &rec[1], &rec[2]
+            ));
+        }
+    }
+
+    if rows != expected_rows {
+        problems.push(format!("{} rows, expected {}", rows, expected_rows));
+    }
+
+    (rows, problems)
+}
+
+pub fn check_file(code: &str, date: &str, expected_rows: usize) -> FileReport {
+    let path = data_dir().join(code).join(format!("{}.csv", date));
+    let (rows, problems) = match File::open(&path) {
+        Ok(f) => check_records(f, expected_rows),
+        Err(e) => (0, vec![format!("{}: {}", e, path.display())]),
+    };
+
+    FileReport {
+        code: code.to_string(),
+        date: date.to_string(),
+        rows,
+        problems,
+    }
+}
+
+// Check every chart file of the region (or of every region directory in the
+// data directory). Fails when any file has a problem.
+pub fn validate(
+    code: Option<&str>,
+    expected_rows: usize,
+    failed_only: bool,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let codes: Vec<String> = match code {
+        Some(code) => vec![code.to_string()],
+        None => available_codes()?,
+    };
+
+    let mut reports = Vec::new();
+    for code in &codes {
+        for date in available_dates(code)? {
+            reports.push(check_file(code, &date, expected_rows));
+        }
+    }
+    let checked = reports.len();
+    let failed = reports.iter().filter(|report| !report.ok()).count();
+    if failed_only {
+        reports.retain(|report| !report.ok());
+    }
+
+    write_report(
+        std::io::stdout(),
+        format,
+        &["Code", "Date", "Rows", "Status", "Problems"],
+        &reports,
+        |report| {
+            vec![
+                report.code.clone(),
+                report.date.clone(),
+                report.rows.to_string(),
+                if report.ok() { "OK" } else { "FAIL" }.to_string(),
+                report.problems.join("; "),
+            ]
+        },
+    )?;
+
+    if failed > 0 {
+        return Err(From::from(format!(
+            "{} of {} files failed validation",
+            failed, checked
+        )));
+    }
+    if format == Format::Text {
+        println!("All {} files passed validation", checked);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clean_file() {
+        let data = "1*As It Was*Harry Styles*1,000\n2*Glimpse of Us*Joji*900\n";
+        let (rows, problems) = check_records(data.as_bytes(), 2);
+        assert_eq!(2, rows);
+        assert!(problems.is_empty());
+    }
+
+    #[test]
+    fn every_problem() {
+        let data = "1*A*X*1,000\n3*B*X*1,200\n4*A*X*oops\n4*C\n";
+        let (rows, problems) = check_records(data.as_bytes(), 200);
+        assert_eq!(4, rows);
+        assert_eq!(
+            vec![
+                "line 2: rank 3, expected 2",
+                "line 2: streams 1200 above the rank before (1000)",
+                "line 3: invalid streams \"oops\"",
+                "line 3: duplicate \"A\" by \"X\"",
+                "line 4: 2 fields, expected 4",
+                "4 rows, expected 200",
+            ],
+            problems
+        );
+    }
+}
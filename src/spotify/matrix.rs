@@ -0,0 +1,270 @@
+use std::{error::Error, io::Write};
+
+use serde::Serialize;
+
+use super::{
+    filter::Filter,
+    fmt::{add_comma, escape_html, Format},
+    regions::Regions,
+    SpotifyChart,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Value {
+    Rank,
+    Streams,
+}
+
+// Tracks as rows, regions as columns. A cell is None when the track is not
+// on that region's chart.
+#[derive(Debug, Serialize)]
+pub struct Matrix {
+    pub date: String,
+    pub value: Value,
+    pub regions: Vec<String>,
+    pub rows: Vec<MatrixRow>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MatrixRow {
+    pub title: String,
+    pub artist: String,
+    // Number of regions charting the track, the global chart not included
+    pub markets: usize,
+    pub cells: Vec<Option<i64>>,
+}
+
+impl Matrix {
+    pub fn from_charts(charts: &[SpotifyChart], filter: &Filter, value: Value) -> Self {
+        let mut rows: Vec<MatrixRow> = Vec::new();
+
+        for (col, chart) in charts.iter().enumerate() {
+            for entry in chart.find_all_where(filter).unwrap_or_default() {
+                let row = match rows
+                    .iter()
+                    .position(|row| row.title == entry.title && row.artist == entry.artist)
+                {
+                    Some(row) => row,
+                    None => {
+                        rows.push(MatrixRow {
+                            title: entry.title.clone(),
+                            artist: entry.artist.clone(),
+                            markets: 0,
+                            cells: vec![None; charts.len()],
+                        });
+                        rows.len() - 1
+                    }
+                };
+
+                rows[row].cells[col] = Some(match value {
+                    Value::Rank => entry.rank as i64,
+                    Value::Streams => entry.streams,
+                });
+                if Regions::from(chart.code.as_str()) != Regions::GLOBAL {
+                    rows[row].markets += 1;
+                }
+            }
+        }
+
+        rows.sort_by(|a, b| b.markets.cmp(&a.markets).then(a.title.cmp(&b.title)));
+
+        Matrix {
+            date: charts
+                .first()
+                .map(|chart| chart.date_string.clone())
+                .unwrap_or_default(),
+            value,
+            regions: charts.iter().map(|chart| chart.region.clone()).collect(),
+            rows,
+        }
+    }
+
+    fn cell_string(&self, cell: Option<i64>) -> String {
+        match (cell, self.value) {
+            (None, _) => String::new(),
+            (Some(rank), Value::Rank) => rank.to_string(),
+            (Some(streams), Value::Streams) => add_comma(streams),
+        }
+    }
+
+    pub fn write<W: Write>(&self, mut w: W, format: Format) -> Result<(), Box<dyn Error>> {
+        match format {
+            Format::Text => {
+                let cells: Vec<Vec<String>> = self
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        row.cells
+                            .iter()
+                            .map(|&cell| match cell {
+                                None => "-".to_string(),
+                                Some(_) => self.cell_string(cell),
+                            })
+                            .collect()
+                    })
+                    .collect();
+                // Each column as wide as its widest cell
+                let widths: Vec<usize> = self
+                    .regions
+                    .iter()
+                    .enumerate()
+                    .map(|(col, region)| {
+                        cells
+                            .iter()
+                            .map(|row| row[col].chars().count())
+                            .fold(region.chars().count(), usize::max)
+                    })
+                    .collect();
+
+                write!(w, "{:30} {:20}", "Title", "Artist")?;
+                for (region, width) in self.regions.iter().zip(&widths) {
+                    write!(w, " {:>w$}", region, w = width)?;
+                }
+                writeln!(w, " {:>7}", "Markets")?;
+
+                for (row, row_cells) in self.rows.iter().zip(&cells) {
+                    write!(w, "{:30.30} {:20.20}", row.title, row.artist)?;
+                    for (cell, width) in row_cells.iter().zip(&widths) {
+                        write!(w, " {:>w$}", cell, w = width)?;
+                    }
+                    writeln!(w, " {:>7}", row.markets)?;
+                }
+            }
+            Format::Csv => {
+                let mut csv_wtr = csv::Writer::from_writer(w);
+                let mut header = vec!["Title".to_string(), "Artist".to_string()];
+                header.extend(self.regions.iter().cloned());
+                header.push("Markets".to_string());
+                csv_wtr.write_record(&header)?;
+
+                for row in &self.rows {
+                    let mut record = vec![row.title.clone(), row.artist.clone()];
+                    // Raw numbers so the file can be loaded without parsing commas
+                    record.extend(
+                        row.cells
+                            .iter()
+                            .map(|cell| cell.map(|num| num.to_string()).unwrap_or_default()),
+                    );
+                    record.push(row.markets.to_string());
+                    csv_wtr.write_record(&record)?;
+                }
+                csv_wtr.flush()?;
+            }
+            Format::Json => {
+                serde_json::to_writer_pretty(&mut w, self)?;
+                writeln!(w)?;
+            }
+            Format::Html => {
+                writeln!(w, "<table>\n<tr>\n\t<th>Title</th>\n\t<th>Artist</th>")?;
+                for region in &self.regions {
+                    writeln!(w, "\t<th class=\"center\">{}</th>", escape_html(region))?;
+                }
+                writeln!(w, "\t<th>Markets</th>\n</tr>")?;
+
+                for row in &self.rows {
+                    writeln!(
+                        w,
+                        "<tr>\n\t<td class=\"first\">{}</td>\n\t<td>{}</td>",
+                        escape_html(&row.title),
+                        escape_html(&row.artist)
+                    )?;
+                    for &cell in &row.cells {
+                        writeln!(w, "\t<td class=\"center\">{}</td>", self.cell_string(cell))?;
+                    }
+                    writeln!(w, "\t<td>{}</td>\n</tr>", row.markets)?;
+                }
+                writeln!(w, "</table>")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn matrix(
+    date: &str,
+    filter: &Filter,
+    value: Value,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let charts = SpotifyChart::from_date_all(date);
+    if charts.is_empty() {
+        return Err(From::from(format!("No chart data found for {}", date)));
+    }
+
+    Matrix::from_charts(&charts, filter, value).write(std::io::stdout(), format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spotify::{fixtures::chart, SpotifyEntry};
+
+    #[test]
+    fn from_charts_rank() -> Result<(), Box<dyn Error>> {
+        let charts = vec![
+            chart(
+                "global",
+                "2022-06-01",
+                vec![SpotifyEntry::new(1, "As It Was", "Harry Styles", 300)],
+            ),
+            chart(
+                "us",
+                "2022-06-01",
+                vec![
+                    SpotifyEntry::new(2, "As It Was", "Harry Styles", 200),
+                    SpotifyEntry::new(7, "Late Night Talking", "Harry Styles", 100),
+                ],
+            ),
+            chart(
+                "gb",
+                "2022-06-01",
+                vec![SpotifyEntry::new(1, "As It Was", "Harry Styles", 100)],
+            ),
+        ];
+
+        let matrix = Matrix::from_charts(&charts, &Filter::parse("artist:harry")?, Value::Rank);
+        assert_eq!(vec!["Global", "US", "UK"], matrix.regions);
+        assert_eq!(2, matrix.rows.len());
+        assert_eq!(vec![Some(1), Some(2), Some(1)], matrix.rows[0].cells);
+        assert_eq!(2, matrix.rows[0].markets);
+        assert_eq!(vec![None, Some(7), None], matrix.rows[1].cells);
+
+        let mut out = Vec::new();
+        matrix.write(&mut out, Format::Csv)?;
+        assert_eq!(
+            "Title,Artist,Global,US,UK,Markets\n\
+             As It Was,Harry Styles,1,2,1,2\n\
+             Late Night Talking,Harry Styles,,7,,1\n",
+            String::from_utf8(out)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn text_columns_fit_streams() -> Result<(), Box<dyn Error>> {
+        let charts = vec![
+            chart(
+                "us",
+                "2022-06-01",
+                vec![SpotifyEntry::new(1, "As It Was", "Harry Styles", 1234567)],
+            ),
+            chart(
+                "gb",
+                "2022-06-01",
+                vec![SpotifyEntry::new(3, "Other", "Harry Styles", 12)],
+            ),
+        ];
+
+        let matrix = Matrix::from_charts(&charts, &Filter::parse("artist:harry")?, Value::Streams);
+        let mut out = Vec::new();
+        matrix.write(&mut out, Format::Text)?;
+        let out = String::from_utf8(out)?;
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(lines[0].ends_with("        US UK Markets"));
+        assert!(lines[1].ends_with(" 1,234,567  -       1"));
+        assert!(lines[2].ends_with("         - 12       1"));
+        Ok(())
+    }
+}
@@ -1,22 +1,56 @@
 #![allow(dead_code)]
 
+pub mod aggregate;
+pub mod alerts;
+pub mod anomaly;
+pub mod artists;
+pub mod combined;
+pub mod curve;
+pub mod debut;
+pub mod digest;
+pub mod feed;
+pub mod filter;
 pub mod find;
+#[cfg(test)]
+mod fixtures;
 pub mod fmt;
+pub mod gaps;
+pub mod history;
+pub mod integrity;
+pub mod matrix;
+pub mod project;
+pub mod records;
 pub mod regions;
+pub mod server;
+pub mod shell;
+pub mod site;
+pub mod stats;
+pub mod summary;
+pub mod svg;
+pub mod tui;
 mod validate;
+pub mod watch;
+pub mod watchlist;
 
-use std::{error::Error, fs::File, num::NonZeroU8};
+use serde::Serialize;
+use std::{error::Error, fs::File, num::NonZeroU8, path::PathBuf};
 use time::{Date, Month};
 
+use self::artists::{parse_credits, Credit, Role};
+use self::filter::{Field, Filter, Pattern};
+use self::fmt::{DailyStyle, Trend};
+use self::history::TrackHistory;
 use self::regions::RegionString;
 use self::validate::{match_date, verify_code, verify_date};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct SpotifyEntry {
     pub rank: i16,
     pub title: String,
     pub artist: String,
     pub streams: i64,
+    #[serde(skip)]
+    pub artists: Vec<Credit>,
 }
 
 impl SpotifyEntry {
@@ -26,18 +60,34 @@ impl SpotifyEntry {
             title: title.to_string(),
             artist: artist.to_string(),
             streams,
+            artists: parse_credits(title, artist),
         }
     }
+
+    // Whether the artist is credited on this entry, featured artists only
+    // count when `features` is set
+    pub fn is_credited(&self, artist: &str, features: bool) -> bool {
+        self.artists
+            .iter()
+            .any(|credit| credit.matches(artist, features))
+    }
+
+    pub fn lead_artists(&self) -> impl Iterator<Item = &str> {
+        self.artists
+            .iter()
+            .filter(|credit| credit.role == Role::Lead)
+            .map(|credit| credit.name.as_str())
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SpotifyChart {
     region: String,
     code: String,
     date: Date,
     date_string: String,
     chart: Vec<SpotifyEntry>,
-    count: u8,
+    count: usize,
 }
 
 impl SpotifyChart {
@@ -67,11 +117,27 @@ impl SpotifyChart {
         })
     }
 
+    // A chart of a region code and date without entries, stands in for a day
+    // that has no data
+    pub fn empty(code: &str, date: &str) -> Result<Self, Box<dyn Error>> {
+        if !verify_code(code) {
+            return Err(From::from(format!("Invalid code: \"{}\"", code)));
+        }
+        Self::spotify_chart_build(
+            regions::Regions::from(code).to_region_string(),
+            code.to_string(),
+            date.to_string(),
+        )
+    }
+
     pub fn from_reader(
         f: File,
         date: &str,
         code: &str,
     ) -> Result<Self, Box<dyn std::error::Error>> {
+        if !verify_code(code) {
+            return Err(From::from(format!("Invalid code: \"{}\"", code)));
+        }
         let mut res = Self::new();
         let mut csv_rdr = csv::ReaderBuilder::new()
             .has_headers(false)
@@ -94,24 +160,66 @@ impl SpotifyChart {
         res.date_string = date.to_string();
         res.date = match_date(date)?.unwrap();
 
-        res.count = res.chart.len() as u8;
+        res.count = res.chart.len();
         Ok(res)
     }
 
+    // Resolve and parse the chart file of a region code on a date
+    pub fn from_code_date(code: &str, date: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let fh = resolve_file_handle(code, date)?;
+        SpotifyChart::from_reader(fh, date, code)
+    }
+
+    // Every region's chart of a date, regions without data are reported and skipped
+    pub fn from_date_all(date: &str) -> Vec<Self> {
+        regions::Regions::regions_vec()
+            .into_iter()
+            .filter_map(|region| {
+                let code = String::from(region);
+                match SpotifyChart::from_code_date(&code, date) {
+                    Ok(chart) => Some(chart),
+                    Err(_) => {
+                        eprintln!("{} - Today[{}] data missing!", code, date);
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    // Charts of a region for every date in the range, dates without data are
+    // reported and skipped
+    pub fn from_code_range(code: &str, from: &str, to: &str) -> Result<Vec<Self>, Box<dyn Error>> {
+        Ok(date_range(from, to)?
+            .into_iter()
+            .filter_map(|date| match SpotifyChart::from_code_date(code, &date) {
+                Ok(chart) => Some(chart),
+                Err(_) => {
+                    eprintln!("{} - Today[{}] data missing!", code, date);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    // Exact title and artist, for following one track across charts
+    pub fn find_exact(&self, title: &str, artist: &str) -> Option<&SpotifyEntry> {
+        self.chart
+            .iter()
+            .find(|&entry| entry.title == title && entry.artist == artist)
+    }
+
     fn find(
         &self,
         title: Option<&str>,
         artist: Option<&str>,
         keyword: Option<&str>,
     ) -> Option<&SpotifyEntry> {
-        let entry = self.find_all(title, artist, keyword);
-        if let None = entry {
-            None
-        } else if let Some(&result) = entry.unwrap().get(0) {
-            Some(result)
-        } else {
-            None
-        }
+        Filter::from_parts(title, artist, keyword).and_then(|filter| self.find_where(&filter))
+    }
+
+    pub fn find_where(&self, filter: &Filter) -> Option<&SpotifyEntry> {
+        self.chart.iter().find(|&entry| filter.matches(entry))
     }
 
     pub fn find_by_title_artist(&self, title: &str, artist: &str) -> Option<&SpotifyEntry> {
@@ -136,94 +244,20 @@ impl SpotifyChart {
         artist: Option<&str>,
         keyword: Option<&str>,
     ) -> Option<Vec<&SpotifyEntry>> {
-        let entry: Option<Vec<&SpotifyEntry>> = match (title, artist, keyword) {
-            (None, None, None) => None,
-            (None, None, Some(keyword)) => Some(
-                self.chart
-                    .iter()
-                    .filter(|&entry| {
-                        (entry.title.to_lowercase().contains(&keyword.to_lowercase()))
-                            || (entry
-                                .artist
-                                .to_lowercase()
-                                .contains(&keyword.to_lowercase()))
-                    })
-                    .collect::<Vec<&SpotifyEntry>>(),
-            ),
-            (None, Some(artist), None) => Some(
-                self.chart
-                    .iter()
-                    .filter(|&entry| entry.artist.to_lowercase().contains(&artist.to_lowercase()))
-                    .collect::<Vec<&SpotifyEntry>>(),
-            ),
-            (None, Some(artist), Some(keyword)) => Some(
-                self.chart
-                    .iter()
-                    .filter(|&entry| {
-                        (entry.title.to_lowercase().contains(&keyword.to_lowercase()))
-                            || (entry
-                                .artist
-                                .to_lowercase()
-                                .contains(&keyword.to_lowercase()))
-                                && (entry.artist.to_lowercase().contains(&artist.to_lowercase()))
-                    })
-                    .collect::<Vec<&SpotifyEntry>>(),
-            ),
-            (Some(title), None, None) => Some(
-                self.chart
-                    .iter()
-                    .filter(|&entry| entry.title.to_lowercase().contains(&title.to_lowercase()))
-                    .collect::<Vec<&SpotifyEntry>>(),
-            ),
-            (Some(title), None, Some(keyword)) => Some(
-                self.chart
-                    .iter()
-                    .filter(|&entry| {
-                        (entry.title.to_lowercase().contains(&keyword.to_lowercase()))
-                            || (entry
-                                .artist
-                                .to_lowercase()
-                                .contains(&keyword.to_lowercase()))
-                                && (entry.title.to_lowercase().contains(&title.to_lowercase()))
-                    })
-                    .collect::<Vec<&SpotifyEntry>>(),
-            ),
-            (Some(title), Some(artist), None) => Some(
-                self.chart
-                    .iter()
-                    .filter(|&entry| {
-                        (entry.title.to_lowercase().contains(&title.to_lowercase()))
-                            && (entry.artist.to_lowercase().contains(&artist.to_lowercase()))
-                    })
-                    .collect::<Vec<&SpotifyEntry>>(),
-            ),
-            (Some(title), Some(artist), Some(keyword)) => Some(
-                self.chart
-                    .iter()
-                    .filter(|&entry| {
-                        (entry.title.to_lowercase().contains(&keyword.to_lowercase()))
-                            || (entry
-                                .artist
-                                .to_lowercase()
-                                .contains(&keyword.to_lowercase()))
-                                && ((entry.title.to_lowercase().contains(&title.to_lowercase()))
-                                    && (entry
-                                        .artist
-                                        .to_lowercase()
-                                        .contains(&artist.to_lowercase())))
-                    })
-                    .collect::<Vec<&SpotifyEntry>>(),
-            ),
-        };
+        Filter::from_parts(title, artist, keyword).and_then(|filter| self.find_all_where(&filter))
+    }
 
-        if let Some(entry) = entry {
-            if entry.len() > 0 {
-                Some(entry)
-            } else {
-                None
-            }
-        } else {
+    pub fn find_all_where(&self, filter: &Filter) -> Option<Vec<&SpotifyEntry>> {
+        let entry = self
+            .chart
+            .iter()
+            .filter(|&entry| filter.matches(entry))
+            .collect::<Vec<&SpotifyEntry>>();
+
+        if entry.is_empty() {
             None
+        } else {
+            Some(entry)
         }
     }
 
@@ -247,6 +281,13 @@ impl SpotifyChart {
         self.find_all(Some(title), None, None)
     }
 
+    // Same as `find_all_by_artist` but lets the caller decide whether songs the
+    // artist is only featured on are included
+    pub fn find_all_by_credit(&self, artist: &str, features: bool) -> Option<Vec<&SpotifyEntry>> {
+        let field = if features { Field::Artist } else { Field::Lead };
+        self.find_all_where(&Filter::Match(field, Pattern::contains(artist)))
+    }
+
     pub fn previous_day(&self) -> Result<SpotifyChart, Box<dyn Error>> {
         let yesterday = get_previous_day(&self.date_string)?;
         let fh1 = resolve_file_handle(&self.code, &yesterday)?;
@@ -256,7 +297,7 @@ impl SpotifyChart {
 
     pub fn previous_chart(&self, date: &str) -> Result<SpotifyChart, Box<dyn Error>> {
         let fh1 = resolve_file_handle(&self.code, date)?;
-        let previous_chart = SpotifyChart::from_reader(fh1, &date, &self.code)?;
+        let previous_chart = SpotifyChart::from_reader(fh1, date, &self.code)?;
         Ok(previous_chart)
     }
 
@@ -270,36 +311,24 @@ impl SpotifyChart {
         let today = self.find(title, artist, keyword);
         let yesterday = previous_chart.find(title, artist, keyword);
 
-        let title = if title.is_some() {
-            title.unwrap()
-        } else {
-            "Unknown"
+        SpotifyGain::from_lookup(
+            today,
+            yesterday,
+            title.unwrap_or("Unknown"),
+            artist.unwrap_or("Unknown"),
+        )
+    }
+
+    // Gain of the first entry matching the filter, the previous chart is
+    // searched for that same song rather than matched against the filter again
+    pub fn song_gain_where(&self, previous_chart: &SpotifyChart, filter: &Filter) -> SpotifyGain {
+        let today = self.find_where(filter);
+        let yesterday = match today {
+            Some(entry) => previous_chart.find_exact(&entry.title, &entry.artist),
+            None => previous_chart.find_where(filter),
         };
 
-        let artist = if artist.is_some() {
-            artist.unwrap()
-        } else {
-            "Unknown"
-        };
-
-        let sp_gain = match (today, yesterday) {
-            (None, None) => SpotifyGain::new(0, 0, title, artist, 0, 0),
-            (None, Some(entry)) => {
-                SpotifyGain::new(0, entry.rank, &entry.title, &entry.artist, 0, entry.streams)
-            }
-            (Some(entry), None) => {
-                SpotifyGain::new(entry.rank, 0, &entry.title, &entry.artist, entry.streams, 0)
-            }
-            (Some(today), Some(yesterday)) => SpotifyGain::new(
-                today.rank,
-                yesterday.rank,
-                &today.title,
-                &today.artist,
-                today.streams,
-                yesterday.streams,
-            ),
-        };
-        sp_gain
+        SpotifyGain::from_lookup(today, yesterday, "Unknown", "Unknown")
     }
 
     pub fn song_gain_all(
@@ -309,28 +338,42 @@ impl SpotifyChart {
         artist: Option<&str>,
         keyword: Option<&str>,
     ) -> Option<Vec<SpotifyGain>> {
-        let today = self.find_all(title, artist, keyword);
+        Filter::from_parts(title, artist, keyword)
+            .and_then(|filter| self.song_gain_all_where(previous_chart, &filter))
+    }
+
+    // Gain of every entry on the chart, matched exactly against the previous
+    // chart so entries not on it show up as new
+    pub fn gains(&self, previous_chart: &SpotifyChart) -> Vec<SpotifyGain> {
+        self.chart
+            .iter()
+            .map(|entry| {
+                let yesterday = previous_chart.find_exact(&entry.title, &entry.artist);
+                SpotifyGain::from_lookup(Some(entry), yesterday, &entry.title, &entry.artist)
+            })
+            .collect()
+    }
+
+    pub fn song_gain_all_where(
+        &self,
+        previous_chart: &SpotifyChart,
+        filter: &Filter,
+    ) -> Option<Vec<SpotifyGain>> {
+        let today = self.find_all_where(filter);
 
-        if let Some(entries) = today {
-            let res = entries
+        today.map(|entries| {
+            entries
                 .into_iter()
                 .map(|entry| {
-                    self.song_gain(
-                        previous_chart,
-                        Some(&entry.title),
-                        Some(&entry.artist),
-                        None,
-                    )
+                    let yesterday = previous_chart.find_exact(&entry.title, &entry.artist);
+                    SpotifyGain::from_lookup(Some(entry), yesterday, &entry.title, &entry.artist)
                 })
-                .collect::<Vec<SpotifyGain>>();
-            Some(res)
-        } else {
-            None
-        }
+                .collect::<Vec<SpotifyGain>>()
+        })
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct SpotifyGain {
     pub today_rank: i16,
     pub yesterday_rank: i16,
@@ -343,6 +386,14 @@ pub struct SpotifyGain {
     pub percent_diff: f64,
 }
 
+// A gain with the region it is from
+#[derive(Debug, Serialize)]
+pub struct RegionGain {
+    pub region: String,
+    pub code: String,
+    pub gain: SpotifyGain,
+}
+
 impl SpotifyGain {
     pub fn new(
         today_rank: i16,
@@ -384,6 +435,33 @@ impl SpotifyGain {
             ))
         }
     }
+    // Build a gain from the entries found in today's and the previous chart,
+    // falling back to the given title and artist when neither chart has it
+    fn from_lookup(
+        today: Option<&SpotifyEntry>,
+        yesterday: Option<&SpotifyEntry>,
+        title: &str,
+        artist: &str,
+    ) -> SpotifyGain {
+        match (today, yesterday) {
+            (None, None) => SpotifyGain::new(0, 0, title, artist, 0, 0),
+            (None, Some(entry)) => {
+                SpotifyGain::new(0, entry.rank, &entry.title, &entry.artist, 0, entry.streams)
+            }
+            (Some(entry), None) => {
+                SpotifyGain::new(entry.rank, 0, &entry.title, &entry.artist, entry.streams, 0)
+            }
+            (Some(today), Some(yesterday)) => SpotifyGain::new(
+                today.rank,
+                yesterday.rank,
+                &today.title,
+                &today.artist,
+                today.streams,
+                yesterday.streams,
+            ),
+        }
+    }
+
     pub fn print<T: Fn(&SpotifyGain, String)>(&self, region: String, pt: T) {
         pt(self, region);
     }
@@ -391,6 +469,89 @@ impl SpotifyGain {
 
 type Record = (i16, String, String, String);
 
+// Width of the bars in the `bars` style
+const BAR_WIDTH: usize = 40;
+
+// Print the daily gains of a track in every region. `spark` is the number of
+// days of streams and rank shown with the `text` style, 0 for none.
+pub fn daily(
+    date: &str,
+    filter: &Filter,
+    style: DailyStyle,
+    spark: usize,
+) -> Result<(), Box<dyn Error>> {
+    if spark > 0 && style != DailyStyle::Text {
+        return Err(From::from("Sparklines are only drawn in the text style!"));
+    }
+    let gains = daily_gains(date, filter)?;
+    match style {
+        DailyStyle::Html => gains
+            .into_iter()
+            .for_each(|gain| gain.gain.print(gain.region, fmt::style2)),
+        DailyStyle::Text if spark == 0 => gains
+            .into_iter()
+            .for_each(|gain| gain.gain.print(gain.region, fmt::style1)),
+        DailyStyle::Text => {
+            let from = add_days(date, 1 - spark as i64)?;
+            for RegionGain { region, code, gain } in &gains {
+                let charts = SpotifyChart::from_code_range(code, &from, date)?;
+                let history =
+                    TrackHistory::from_charts_exact(&charts, &gain.title, &gain.artist, false);
+                // Missing days stay blank, so the sparkline keeps its width
+                let mut trend = Trend::default();
+                for day in date_range(&from, date)? {
+                    let point = history.points.iter().find(|point| point.date == day);
+                    trend.streams.push(
+                        point
+                            .filter(|point| point.rank > 0)
+                            .map(|point| point.streams),
+                    );
+                    trend.ranks.push(point.map_or(0, |point| point.rank));
+                }
+                println!("{}", fmt::text_row(gain, region, Some(&trend)));
+            }
+        }
+        DailyStyle::Bars => {
+            let max = gains.first().map_or(0, |gain| gain.gain.today_streams);
+            for gain in &gains {
+                println!("{}", fmt::bar_row(&gain.gain, &gain.region, max, BAR_WIDTH));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Gain of the first entry matching the filter in every region on a date,
+// most streamed region first
+pub fn daily_gains(date: &str, filter: &Filter) -> Result<Vec<RegionGain>, Box<dyn Error>> {
+    let mut gains: Vec<RegionGain> = Vec::new();
+    for code in regions::Regions::regions_vec() {
+        let region = code.to_region_string();
+        let code = String::from(code);
+
+        if let Ok(f) = resolve_file_handle(code.as_str(), date) {
+            let chart = SpotifyChart::from_reader(f, date, &code)?;
+            if let Ok(previous_chart) = chart.previous_day() {
+                let gain = chart.song_gain_where(&previous_chart, filter);
+
+                if gain.today_rank != 0 {
+                    gains.push(RegionGain { region, code, gain });
+                } else {
+                    eprintln!("{:#?}", gain);
+                }
+            } else {
+                eprintln!("{} - Previous day[-] data missing!", code);
+            }
+        } else {
+            eprintln!("{} - Today[{}] data missing!", code, date);
+        }
+    }
+
+    gains.sort_by_key(|gain| gain.gain.today_streams);
+    gains.reverse();
+    Ok(gains)
+}
+
 pub fn from_reader(f: File) -> Result<Vec<SpotifyEntry>, Box<dyn std::error::Error>> {
     let mut res = Vec::new();
     let mut csv_rdr = csv::ReaderBuilder::new()
@@ -432,18 +593,58 @@ pub fn parse_int(num: &str) -> Result<i64, Box<dyn Error>> {
     Ok(res as i64)
 }
 
+const DATA_DIR: &str = "/home/ubuntu/project/chartscan/SpotifyData";
+
+// Root of the chart archive, laid out as {code}/{date}.csv. Can be moved with
+// the CHARTSCAN_DATA environment variable.
+pub fn data_dir() -> PathBuf {
+    match std::env::var_os("CHARTSCAN_DATA") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(DATA_DIR),
+    }
+}
+
+// Sorted region codes that have a directory in the data directory
+pub fn available_codes() -> Result<Vec<String>, Box<dyn Error>> {
+    let dir = data_dir();
+    let mut codes = Vec::new();
+    for entry in std::fs::read_dir(&dir).map_err(|e| format!("{}: {}", e, dir.display()))? {
+        let entry = entry?;
+        if let Some(name) = entry.file_name().to_str() {
+            if entry.file_type()?.is_dir() && verify_code(name) {
+                codes.push(name.to_string());
+            }
+        }
+    }
+    codes.sort();
+    Ok(codes)
+}
+
+// Sorted dates that have a chart file for the region code
+pub fn available_dates(code: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let dir = data_dir().join(code);
+    let mut dates = Vec::new();
+    for file in std::fs::read_dir(&dir).map_err(|e| format!("{}: {}", e, dir.display()))? {
+        let name = file?.file_name();
+        if let Some(date) = name.to_str().and_then(|name| name.strip_suffix(".csv")) {
+            if verify_date(date) {
+                dates.push(date.to_string());
+            }
+        }
+    }
+    dates.sort();
+    Ok(dates)
+}
+
 // Verify code and date is valid, check whther a file with this code and date exists
 pub fn resolve_file_handle(code: &str, date: &str) -> Result<File, Box<dyn std::error::Error>> {
     match (verify_code(code), verify_date(date)) {
         // Only attempt to open the file when both code and date are valid
         (true, true) => {
-            let path = format!(
-                "/home/ubuntu/project/chartscan/SpotifyData/{}/{}.csv",
-                code, date
-            );
-            match File::open(path.clone()) {
+            let path = data_dir().join(code).join(format!("{}.csv", date));
+            match File::open(&path) {
                 Ok(f) => Ok(f),
-                Err(e) => Err(From::from(format!("{}: {}", e, path))),
+                Err(e) => Err(From::from(format!("{}: {}", e, path.display()))),
             }
         }
         // Code is valid but date is invalid
@@ -457,21 +658,60 @@ pub fn resolve_file_handle(code: &str, date: &str) -> Result<File, Box<dyn std::
 }
 
 pub fn get_previous_day(date: &str) -> Result<String, Box<dyn Error>> {
-    if let Some(date) = match_date(date).unwrap() {
+    if let Some(date) = match_date(date)? {
         let previous = date.previous_day().unwrap();
-        Ok(format!(
-            "{}-{:02}-{:02}",
-            previous.year(),
-            previous.month() as u8,
-            previous.day(),
-        ))
+        Ok(date_to_string(previous))
     } else {
         Err(From::from(format!("Invalid date string: {}", date)))
     }
 }
 
+pub fn date_to_string(date: Date) -> String {
+    format!(
+        "{}-{:02}-{:02}",
+        date.year(),
+        date.month() as u8,
+        date.day(),
+    )
+}
+
+// Date string moved by a number of days, negative to go back
+pub fn add_days(date: &str, days: i64) -> Result<String, Box<dyn Error>> {
+    match match_date(date)? {
+        Some(date) => Ok(date_to_string(date + time::Duration::days(days))),
+        None => Err(From::from(format!("Invalid date string: {}", date))),
+    }
+}
+
+// Every date from `from` to `to`, both included
+pub fn date_range(from: &str, to: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    if !verify_date(from) || !verify_date(to) {
+        return Err(From::from(format!(
+            "Invalid date range: {} -- {}",
+            from, to
+        )));
+    }
+
+    let mut date = match_date(from)?.unwrap();
+    let end = match_date(to)?.unwrap();
+    if date > end {
+        return Err(From::from(format!(
+            "Start date is after end date: {} -- {}",
+            from, to
+        )));
+    }
+
+    let mut dates = vec![];
+    while date <= end {
+        dates.push(date_to_string(date));
+        date = date.next_day().unwrap();
+    }
+    Ok(dates)
+}
+
 #[cfg(test)]
 mod moretest {
+    use super::fixtures::{chart, ranked};
     use super::*;
 
     type MyResult<T> = Result<T, Box<dyn Error>>;
@@ -550,6 +790,66 @@ mod moretest {
         Ok(())
     }
 
+    #[test]
+    fn date_range_month_end() -> MyResult<()> {
+        assert_eq!(
+            vec!["2022-05-30", "2022-05-31", "2022-06-01"],
+            date_range("2022-05-30", "2022-06-01")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn date_range_single() -> MyResult<()> {
+        assert_eq!(vec!["2022-06-01"], date_range("2022-06-01", "2022-06-01")?);
+        Ok(())
+    }
+
+    #[test]
+    fn date_range_reversed() -> MyResult<()> {
+        assert!(date_range("2022-06-02", "2022-06-01").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn gain_where_follows_one_song() -> MyResult<()> {
+        let today = chart(
+            "us",
+            "2022-07-02",
+            ranked(&[("Stay", 3000), ("Me Porto Bonito", 2900)]),
+        );
+        let previous = chart(
+            "us",
+            "2022-07-01",
+            ranked(&[("Me Porto Bonito", 2981), ("Stay", 2800)]),
+        );
+
+        let gain = today.song_gain_where(&previous, &Filter::parse("rank=1")?);
+        assert_eq!("Stay", gain.title);
+        assert_eq!((1, 2), (gain.today_rank, gain.yesterday_rank));
+        assert_eq!(2800, gain.yesterday_streams);
+
+        let gains = today
+            .song_gain_all_where(&previous, &Filter::parse("rank<=2")?)
+            .unwrap();
+        assert_eq!(
+            vec![1, -1],
+            gains.iter().map(|gain| gain.rank_diff).collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn empty_chart() -> MyResult<()> {
+        let chart = SpotifyChart::empty("gb", "2022-06-01")?;
+        assert_eq!("UK", chart.region);
+        assert_eq!("2022-06-01", chart.date_string);
+        assert!(chart.chart.is_empty());
+        assert!(SpotifyChart::empty("oo", "2022-06-01").is_err());
+        assert!(SpotifyChart::empty("gb", "2022-13-01").is_err());
+        Ok(())
+    }
+
     #[test]
     fn verify_date_20001_01_27_invalid() -> MyResult<()> {
         assert!(!verify_date("20001-01-27"));
@@ -0,0 +1,274 @@
+use std::error::Error;
+
+use serde::Serialize;
+
+use super::{
+    add_days,
+    filter::Filter,
+    fmt::{add_comma, write_report, Format},
+    history::TrackHistory,
+    SpotifyChart,
+};
+
+// Projections give up after this many days
+const MAX_DAYS: i64 = 3650;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Model {
+    // Daily streams change by the same amount every day
+    Linear,
+    // Daily streams change by the same factor every day
+    Exponential,
+}
+
+// Least squares trend of daily streams over day index 0..n, for the
+// exponential model the fit is done on ln(streams)
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrendFit {
+    pub model: Model,
+    pub intercept: f64,
+    pub slope: f64,
+    // Standard error of the slope, used for the confidence range
+    pub slope_se: f64,
+    pub days: usize,
+}
+
+impl TrendFit {
+    pub fn fit(model: Model, streams: &[i64]) -> Option<Self> {
+        let points: Vec<(f64, f64)> = streams
+            .iter()
+            .enumerate()
+            .filter(|&(_, &streams)| model == Model::Linear || streams > 0)
+            .map(|(t, &streams)| {
+                let y = match model {
+                    Model::Linear => streams as f64,
+                    Model::Exponential => (streams as f64).ln(),
+                };
+                (t as f64, y)
+            })
+            .collect();
+        if points.len() < 3 {
+            return None;
+        }
+
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let sxx: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+        let sxy: f64 = points
+            .iter()
+            .map(|(x, y)| (x - mean_x) * (y - mean_y))
+            .sum();
+
+        let slope = sxy / sxx;
+        let intercept = mean_y - slope * mean_x;
+        let sse: f64 = points
+            .iter()
+            .map(|(x, y)| (y - intercept - slope * x).powi(2))
+            .sum();
+        let slope_se = (sse / (n - 2f64) / sxx).sqrt();
+
+        Some(TrendFit {
+            model,
+            intercept,
+            slope,
+            slope_se,
+            days: streams.len(),
+        })
+    }
+
+    // Daily streams `ahead` days after the last fitted day, with the trend
+    // anchored at the fitted value of the last day
+    pub fn predict(&self, ahead: f64, slope: f64) -> f64 {
+        let last = (self.days - 1) as f64;
+        let anchor = self.intercept + self.slope * last;
+        let value = match self.model {
+            Model::Linear => anchor + slope * ahead,
+            Model::Exponential => (anchor + slope * ahead).exp(),
+        };
+        value.max(0f64)
+    }
+
+    // Days after the last fitted day until `remaining` more streams are reached
+    pub fn days_until(&self, remaining: i64, slope: f64) -> Option<i64> {
+        let mut total = 0f64;
+        for day in 1..=MAX_DAYS {
+            total += self.predict(day as f64, slope);
+            if total >= remaining as f64 {
+                return Some(day);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Projection {
+    pub milestone: i64,
+    // Date the milestone is (or was) crossed, None when not within MAX_DAYS
+    pub date: Option<String>,
+    pub earliest: Option<String>,
+    pub latest: Option<String>,
+    pub reached: bool,
+}
+
+// Project milestone dates from a track's cumulative streams. `base` is added
+// to the charted streams for anything streamed before the archive starts,
+// estimated off-chart streams count too when the history has them.
+pub fn project_milestones(
+    history: &TrackHistory,
+    base: i64,
+    fit: &TrendFit,
+    milestones: &[i64],
+) -> Result<Vec<Projection>, Box<dyn Error>> {
+    let last_date = &history.points.last().ok_or("Empty track history!")?.date;
+    // 95% range of the slope
+    let (low, high) = (
+        fit.slope - 1.96 * fit.slope_se,
+        fit.slope + 1.96 * fit.slope_se,
+    );
+
+    let mut projections = Vec::new();
+    for &milestone in milestones {
+        let mut cumulative = base;
+        let crossed = history.points.iter().find(|point| {
+            cumulative += point.streams;
+            cumulative >= milestone
+        });
+
+        let projection = match crossed {
+            Some(point) => Projection {
+                milestone,
+                date: Some(point.date.clone()),
+                earliest: Some(point.date.clone()),
+                latest: Some(point.date.clone()),
+                reached: true,
+            },
+            None => {
+                let remaining = milestone - cumulative;
+                let project = |slope: f64| {
+                    fit.days_until(remaining, slope)
+                        .map(|days| add_days(last_date, days))
+                        .transpose()
+                };
+                Projection {
+                    milestone,
+                    date: project(fit.slope)?,
+                    // More streams per day reach the milestone earlier
+                    earliest: project(high)?,
+                    latest: project(low)?,
+                    reached: false,
+                }
+            }
+        };
+        projections.push(projection);
+    }
+
+    Ok(projections)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn project(
+    code: &str,
+    from: &str,
+    to: &str,
+    filter: &Filter,
+    estimate: bool,
+    model: Model,
+    days: usize,
+    base: i64,
+    milestones: &[i64],
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let charts = SpotifyChart::from_code_range(code, from, to)?;
+    let history = TrackHistory::from_charts(&charts, filter, estimate).ok_or(format!(
+        "No track matching where<{}> in {} from {} to {}",
+        filter, code, from, to
+    ))?;
+
+    let recent: Vec<i64> = history
+        .points
+        .iter()
+        .rev()
+        .take(days)
+        .rev()
+        .map(|point| point.streams)
+        .collect();
+    let fit = TrendFit::fit(model, &recent).ok_or("Not enough days to fit a trend!")?;
+    let projections = project_milestones(&history, base, &fit, milestones)?;
+
+    if format == Format::Text {
+        let trend = match model {
+            Model::Linear => format!("{:+.0} streams/day", fit.slope),
+            Model::Exponential => format!("{:+.2}%/day", (fit.slope.exp() - 1f64) * 100f64),
+        };
+        let estimated = if estimate {
+            format!(" estimated<{}>", add_comma(history.estimated_streams()))
+        } else {
+            String::new()
+        };
+        println!(
+            "Project: title<\"{}\"> artist<\"{}\"> code<{}> total<{}>{} trend<{}>",
+            history.title,
+            history.artist,
+            code,
+            add_comma(base + history.total_streams()),
+            estimated,
+            trend
+        );
+    }
+
+    let date_string = |date: &Option<String>| date.clone().unwrap_or_else(|| "-".to_string());
+    write_report(
+        std::io::stdout(),
+        format,
+        &["Milestone", "Date", "Earliest", "Latest"],
+        &projections,
+        |projection| {
+            vec![
+                add_comma(projection.milestone),
+                if projection.reached {
+                    format!("{} (reached)", date_string(&projection.date))
+                } else {
+                    date_string(&projection.date)
+                },
+                date_string(&projection.earliest),
+                date_string(&projection.latest),
+            ]
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spotify::fixtures::streams_history;
+
+    #[test]
+    fn fit_linear() {
+        let fit = TrendFit::fit(Model::Linear, &[100, 90, 80, 70]).unwrap();
+        assert!((fit.slope + 10f64).abs() < 1e-9);
+        assert!(fit.slope_se.abs() < 1e-9);
+        assert!((fit.predict(1f64, fit.slope) - 60f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fit_exponential() {
+        let fit = TrendFit::fit(Model::Exponential, &[1000, 500, 250, 125]).unwrap();
+        assert!((fit.slope.exp() - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn milestones_constant() -> Result<(), Box<dyn Error>> {
+        let history = streams_history(&[100; 5]);
+        let fit = TrendFit::fit(Model::Linear, &[100; 5]).unwrap();
+        let projections = project_milestones(&history, 0, &fit, &[300, 1000])?;
+
+        assert!(projections[0].reached);
+        assert_eq!(Some("2022-06-03".to_string()), projections[0].date);
+        assert!(!projections[1].reached);
+        assert_eq!(Some("2022-06-10".to_string()), projections[1].date);
+        Ok(())
+    }
+}
@@ -0,0 +1,350 @@
+use std::{collections::HashMap, error::Error};
+
+use serde::Serialize;
+
+use super::{
+    artists::Role,
+    date_range,
+    fmt::{write_report, Format},
+    SpotifyChart,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Category {
+    // Longest run of consecutive days at #1
+    NumberOne,
+    // Longest run of consecutive days in the top 10
+    Top10,
+    // Longest run of consecutive days on the chart
+    OnChart,
+    // Current run at #1 on the last day
+    Current,
+    // Most days at #1 in total
+    MostNumberOne,
+    // Most days in the top 10 in total
+    MostTop10,
+    // Fewest days from debut to first #1
+    Fastest,
+}
+
+impl Category {
+    pub fn all() -> Vec<Category> {
+        vec![
+            Category::NumberOne,
+            Category::Top10,
+            Category::OnChart,
+            Category::Current,
+            Category::MostNumberOne,
+            Category::MostTop10,
+            Category::Fastest,
+        ]
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Category::NumberOne => "Longest #1 streak",
+            Category::Top10 => "Longest top 10 streak",
+            Category::OnChart => "Longest chart streak",
+            Category::Current => "Current #1 streak",
+            Category::MostNumberOne => "Most days at #1",
+            Category::MostTop10 => "Most days in top 10",
+            Category::Fastest => "Fastest to #1",
+        }
+    }
+}
+
+// Consecutive days meeting a condition, as indices into the chart dates
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Streak {
+    pub total: usize,
+    pub current: usize,
+    pub longest: usize,
+    // First day of the longest streak, the latest one on a tie
+    pub longest_start: usize,
+}
+
+impl Streak {
+    fn step(&mut self, day: usize, hit: bool) {
+        if hit {
+            self.total += 1;
+            self.current += 1;
+            if self.current >= self.longest {
+                self.longest = self.current;
+                self.longest_start = day + 1 - self.current;
+            }
+        } else {
+            self.current = 0;
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RecordStats {
+    pub number_one: Streak,
+    pub top10: Streak,
+    pub on_chart: Streak,
+    // Day index of the debut and the first #1. A track already on the chart
+    // on the first day has no known debut.
+    pub debut: Option<usize>,
+    pub first_number_one: Option<usize>,
+}
+
+impl RecordStats {
+    // `ranks` holds the rank on every chart date, None when off the chart
+    pub fn from_ranks(ranks: &[Option<i16>]) -> Self {
+        let mut stats = RecordStats::default();
+        for (day, rank) in ranks.iter().enumerate() {
+            let rank = rank.unwrap_or(0);
+            stats.number_one.step(day, rank == 1);
+            stats.top10.step(day, (1..=10).contains(&rank));
+            stats.on_chart.step(day, rank >= 1);
+
+            if rank >= 1 && stats.on_chart.total == 1 && day > 0 {
+                stats.debut = Some(day);
+            }
+            if rank == 1 && stats.first_number_one.is_none() {
+                stats.first_number_one = Some(day);
+            }
+        }
+        stats
+    }
+
+    pub fn days_to_number_one(&self) -> Option<usize> {
+        match (self.debut, self.first_number_one) {
+            (Some(debut), Some(number_one)) => Some(number_one - debut),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecordRow {
+    pub category: Category,
+    pub name: String,
+    pub days: usize,
+    pub from: String,
+    pub to: String,
+}
+
+// Rank timeline of every track (or artist) over the calendar days, keyed by
+// the name shown in the records. An artist takes the best rank of their songs
+// each day. Days without a chart stay None, so they break every streak.
+pub fn rank_timelines(
+    charts: &[SpotifyChart],
+    dates: &[String],
+    artists: bool,
+    features: bool,
+) -> HashMap<String, Vec<Option<i16>>> {
+    let days: HashMap<&str, usize> = dates
+        .iter()
+        .enumerate()
+        .map(|(day, date)| (date.as_str(), day))
+        .collect();
+    let mut timelines: HashMap<String, Vec<Option<i16>>> = HashMap::new();
+
+    for chart in charts {
+        let Some(&day) = days.get(chart.date_string.as_str()) else {
+            continue;
+        };
+        for entry in &chart.chart {
+            let names: Vec<String> = if artists {
+                entry
+                    .artists
+                    .iter()
+                    .filter(|credit| features || credit.role == Role::Lead)
+                    .map(|credit| credit.name.clone())
+                    .collect()
+            } else {
+                vec![format!("{} - {}", entry.title, entry.artist)]
+            };
+
+            for name in names {
+                let ranks = timelines
+                    .entry(name)
+                    .or_insert_with(|| vec![None; dates.len()]);
+                ranks[day] = Some(ranks[day].map_or(entry.rank, |rank| rank.min(entry.rank)));
+            }
+        }
+    }
+
+    timelines
+}
+
+pub fn compute_records(
+    charts: &[SpotifyChart],
+    categories: &[Category],
+    artists: bool,
+    features: bool,
+    limit: usize,
+) -> Result<Vec<RecordRow>, Box<dyn Error>> {
+    // Every calendar day from the first chart to the last one
+    let dates = match (charts.first(), charts.last()) {
+        (Some(first), Some(last)) => date_range(&first.date_string, &last.date_string)?,
+        _ => return Ok(Vec::new()),
+    };
+    let stats: Vec<(String, RecordStats)> = rank_timelines(charts, &dates, artists, features)
+        .into_iter()
+        .map(|(name, ranks)| (name, RecordStats::from_ranks(&ranks)))
+        .collect();
+
+    let mut rows = Vec::new();
+    for &category in categories {
+        // (days, from, to) of every name for this category
+        let mut candidates: Vec<(&str, usize, usize, usize)> = stats
+            .iter()
+            .filter_map(|(name, stats)| {
+                let streak = |streak: &Streak| {
+                    (
+                        streak.longest,
+                        streak.longest_start,
+                        streak.longest_start + streak.longest.max(1) - 1,
+                    )
+                };
+                let (days, from, to) = match category {
+                    Category::NumberOne => streak(&stats.number_one),
+                    Category::Top10 => streak(&stats.top10),
+                    Category::OnChart => streak(&stats.on_chart),
+                    Category::Current => {
+                        let current = stats.number_one.current;
+                        (current, dates.len() - current.max(1), dates.len() - 1)
+                    }
+                    Category::MostNumberOne => (stats.number_one.total, 0, dates.len() - 1),
+                    Category::MostTop10 => (stats.top10.total, 0, dates.len() - 1),
+                    Category::Fastest => {
+                        let days = stats.days_to_number_one()?;
+                        (days, stats.debut?, stats.first_number_one?)
+                    }
+                };
+                if days == 0 && category != Category::Fastest {
+                    return None;
+                }
+                Some((name.as_str(), days, from, to))
+            })
+            .collect();
+
+        match category {
+            Category::Fastest => candidates.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(b.0))),
+            _ => candidates.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0))),
+        }
+
+        rows.extend(
+            candidates
+                .into_iter()
+                .take(limit)
+                .map(|(name, days, from, to)| RecordRow {
+                    category,
+                    name: name.to_string(),
+                    days,
+                    from: dates[from].clone(),
+                    to: dates[to].clone(),
+                }),
+        );
+    }
+
+    Ok(rows)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn records(
+    code: &str,
+    from: &str,
+    to: &str,
+    categories: &[Category],
+    artists: bool,
+    features: bool,
+    limit: usize,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let charts = SpotifyChart::from_code_range(code, from, to)?;
+    if charts.is_empty() {
+        return Err(From::from(format!(
+            "No chart data found for {} from {} to {}",
+            code, from, to
+        )));
+    }
+
+    let rows = compute_records(&charts, categories, artists, features, limit)?;
+    if format == Format::Text {
+        println!(
+            "Records: code<{}> from<{}> to<{}> days<{}>",
+            code,
+            from,
+            to,
+            charts.len()
+        );
+    }
+    write_report(
+        std::io::stdout(),
+        format,
+        &[
+            "Category",
+            if artists { "Artist" } else { "Track" },
+            "Days",
+            "From",
+            "To",
+        ],
+        &rows,
+        |row| {
+            vec![
+                row.category.describe().to_string(),
+                row.name.clone(),
+                row.days.to_string(),
+                row.from.clone(),
+                row.to.clone(),
+            ]
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streaks_from_ranks() {
+        let stats = RecordStats::from_ranks(&[
+            None,
+            Some(5),
+            Some(1),
+            Some(1),
+            Some(12),
+            Some(1),
+            Some(1),
+            Some(1),
+        ]);
+
+        assert_eq!(5, stats.number_one.total);
+        assert_eq!(3, stats.number_one.longest);
+        assert_eq!(5, stats.number_one.longest_start);
+        assert_eq!(3, stats.number_one.current);
+        assert_eq!(3, stats.top10.longest);
+        assert_eq!(7, stats.on_chart.longest);
+        assert_eq!(Some(1), stats.days_to_number_one());
+    }
+
+    #[test]
+    fn no_debut_on_first_day() {
+        let stats = RecordStats::from_ranks(&[Some(3), Some(1)]);
+        assert_eq!(None, stats.days_to_number_one());
+    }
+
+    #[test]
+    fn missing_date_breaks_streak() -> Result<(), Box<dyn Error>> {
+        use crate::spotify::fixtures::{chart, titles};
+
+        // #1 on the 1st, 2nd and 4th, the 3rd is missing from the archive
+        let charts: Vec<SpotifyChart> = ["2022-05-01", "2022-05-02", "2022-05-04"]
+            .iter()
+            .map(|date| chart("us", date, titles(&["A"])))
+            .collect();
+        let rows = compute_records(&charts, &[Category::NumberOne], false, false, 1)?;
+
+        assert_eq!(2, rows[0].days);
+        assert_eq!(
+            ("2022-05-01", "2022-05-02"),
+            (rows[0].from.as_str(), rows[0].to.as_str())
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,387 @@
+use std::{collections::HashMap, error::Error};
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use super::{
+    date_range,
+    filter::Filter,
+    get_previous_day,
+    history::TrackHistory,
+    regions::{RegionString, Regions},
+    validate::{match_date, verify_code, verify_date},
+    RegionGain, SpotifyChart, SpotifyGain,
+};
+
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 200;
+// Longest /history range, so one request cannot load the whole archive
+const MAX_HISTORY_DAYS: usize = 366;
+
+#[derive(Debug, PartialEq)]
+pub enum ApiError {
+    BadRequest(String),
+    NotFound(String),
+    MethodNotAllowed,
+}
+
+impl ApiError {
+    pub fn status(&self) -> u16 {
+        match self {
+            ApiError::BadRequest(_) => 400,
+            ApiError::NotFound(_) => 404,
+            ApiError::MethodNotAllowed => 405,
+        }
+    }
+
+    fn message(&self) -> &str {
+        match self {
+            ApiError::BadRequest(message) | ApiError::NotFound(message) => message,
+            ApiError::MethodNotAllowed => "Only GET requests are supported",
+        }
+    }
+}
+
+type ApiResult = Result<Value, ApiError>;
+
+fn bad_request(e: Box<dyn Error>) -> ApiError {
+    ApiError::BadRequest(e.to_string())
+}
+
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub items: Vec<T>,
+}
+
+// Decode `%XX` escapes and `+` as a space
+pub fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// Split a request url into its decoded path segments and query parameters
+pub fn parse_url(url: &str) -> (Vec<String>, HashMap<String, String>) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let segments = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(percent_decode)
+        .collect();
+    let params = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect();
+    (segments, params)
+}
+
+fn param_usize(
+    params: &HashMap<String, String>,
+    key: &str,
+    default: usize,
+) -> Result<usize, ApiError> {
+    match params.get(key) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| ApiError::BadRequest(format!("Invalid {}: \"{}\"", key, value))),
+        None => Ok(default),
+    }
+}
+
+fn required<'a>(params: &'a HashMap<String, String>, key: &str) -> Result<&'a str, ApiError> {
+    params
+        .get(key)
+        .map(|value| value.as_str())
+        .ok_or_else(|| ApiError::BadRequest(format!("Missing parameter: {}", key)))
+}
+
+pub fn paginate<T>(items: Vec<T>, params: &HashMap<String, String>) -> Result<Page<T>, ApiError> {
+    let offset = param_usize(params, "offset", 0)?;
+    let limit = param_usize(params, "limit", DEFAULT_LIMIT)?.min(MAX_LIMIT);
+    let total = items.len();
+    Ok(Page {
+        total,
+        offset,
+        limit,
+        items: items.into_iter().skip(offset).take(limit).collect(),
+    })
+}
+
+// Filter from the `title`, `artist` and `where` parameters
+fn filter(params: &HashMap<String, String>) -> Result<Option<Filter>, ApiError> {
+    Filter::from_options(
+        params.get("title").map(|s| s.as_str()),
+        params.get("artist").map(|s| s.as_str()),
+        None,
+        params.get("where").map(|s| s.as_str()),
+    )
+    .map_err(bad_request)
+}
+
+fn required_filter(params: &HashMap<String, String>) -> Result<Filter, ApiError> {
+    filter(params)?.ok_or_else(|| {
+        ApiError::BadRequest("Either one of title, artist or where need to be specified!".into())
+    })
+}
+
+// Dates have to be real calendar days, not just look like one
+fn check_date(date: &str) -> Result<(), ApiError> {
+    if !verify_date(date) {
+        return Err(ApiError::BadRequest(format!("Invalid date: \"{}\"", date)));
+    }
+    match match_date(date) {
+        Ok(Some(_)) => Ok(()),
+        _ => Err(ApiError::BadRequest(format!("Invalid date: \"{}\"", date))),
+    }
+}
+
+fn load_chart(code: &str, date: &str) -> Result<SpotifyChart, ApiError> {
+    if !verify_code(code) {
+        return Err(ApiError::NotFound(format!("Unknown region code: {}", code)));
+    }
+    check_date(date)?;
+    SpotifyChart::from_code_date(code, date)
+        .map_err(|_| ApiError::NotFound(format!("No chart for {} on {}", code, date)))
+}
+
+fn to_value<T: Serialize>(value: T) -> ApiResult {
+    serde_json::to_value(value).map_err(|e| ApiError::BadRequest(e.to_string()))
+}
+
+// GET /charts/{code}/{date}
+fn chart(code: &str, date: &str, params: &HashMap<String, String>) -> ApiResult {
+    let chart = load_chart(code, date)?;
+    let page = paginate(chart.chart.iter().collect(), params)?;
+    Ok(json!({
+        "region": chart.region,
+        "code": chart.code,
+        "date": chart.date_string,
+        "chart": page,
+    }))
+}
+
+// GET /find/{code}/{date}?title=&artist=&where=
+fn find(code: &str, date: &str, params: &HashMap<String, String>) -> ApiResult {
+    let filter = required_filter(params)?;
+    let chart = load_chart(code, date)?;
+    to_value(paginate(
+        chart.find_all_where(&filter).unwrap_or_default(),
+        params,
+    )?)
+}
+
+// GET /gain/{code}/{date}?previous=&title=&artist=&where=
+// Against the previous day unless another date is given
+fn gain(code: &str, date: &str, params: &HashMap<String, String>) -> ApiResult {
+    check_date(date)?;
+    let previous_date = match params.get("previous") {
+        Some(previous) => previous.clone(),
+        None => get_previous_day(date).map_err(bad_request)?,
+    };
+    check_date(&previous_date)?;
+    let chart = load_chart(code, date)?;
+    let previous_chart = load_chart(code, &previous_date)?;
+
+    let filter = filter(params)?;
+    let gains: Vec<SpotifyGain> = chart
+        .gains(&previous_chart)
+        .into_iter()
+        .filter(|gain| {
+            filter.as_ref().is_none_or(|filter| {
+                chart
+                    .find_exact(&gain.title, &gain.artist)
+                    .is_some_and(|entry| filter.matches(entry))
+            })
+        })
+        .collect();
+    to_value(paginate(gains, params)?)
+}
+
+// GET /history/{code}?from=&to=&title=&artist=&where=&estimate=true
+// Both dates are required and at most MAX_HISTORY_DAYS apart
+fn history(code: &str, params: &HashMap<String, String>) -> ApiResult {
+    if !verify_code(code) {
+        return Err(ApiError::NotFound(format!("Unknown region code: {}", code)));
+    }
+    let (from, to) = (required(params, "from")?, required(params, "to")?);
+    check_date(from)?;
+    check_date(to)?;
+    let days = date_range(from, to).map_err(bad_request)?.len();
+    if days > MAX_HISTORY_DAYS {
+        return Err(ApiError::BadRequest(format!(
+            "Range from {} to {} is {} days, at most {} are allowed",
+            from, to, days, MAX_HISTORY_DAYS
+        )));
+    }
+    let filter = required_filter(params)?;
+    let estimate = params.get("estimate").is_some_and(|value| value == "true");
+
+    let charts = SpotifyChart::from_code_range(code, from, to).map_err(bad_request)?;
+    if charts.is_empty() {
+        return Err(ApiError::NotFound(format!(
+            "No chart data found for {} from {} to {}",
+            code, from, to
+        )));
+    }
+    let history = TrackHistory::from_charts(&charts, &filter, estimate).ok_or_else(|| {
+        ApiError::NotFound(format!(
+            "No track matching where<{}> in {} from {} to {}",
+            filter, code, from, to
+        ))
+    })?;
+    to_value(history)
+}
+
+// GET /daily/{date}?title=&artist=&where=
+// The first matching entry of every region that has both days
+fn daily(date: &str, params: &HashMap<String, String>) -> ApiResult {
+    let filter = required_filter(params)?;
+    check_date(date)?;
+
+    let mut gains: Vec<RegionGain> = Regions::regions_vec()
+        .into_iter()
+        .filter_map(|region| {
+            let region_string = region.to_region_string();
+            let code = String::from(region);
+            let chart = SpotifyChart::from_code_date(&code, date).ok()?;
+            let previous_chart = chart.previous_day().ok()?;
+            let gain = chart.song_gain_where(&previous_chart, &filter);
+            (gain.today_rank != 0).then_some(RegionGain {
+                region: region_string,
+                code,
+                gain,
+            })
+        })
+        .collect();
+    if gains.is_empty() {
+        return Err(ApiError::NotFound(format!(
+            "No track matching where<{}> on {}",
+            filter, date
+        )));
+    }
+    gains.sort_by_key(|gain| std::cmp::Reverse(gain.gain.today_streams));
+    to_value(paginate(gains, params)?)
+}
+
+// Answer a GET request url
+pub fn route(url: &str) -> ApiResult {
+    let (segments, params) = parse_url(url);
+    let segments: Vec<&str> = segments.iter().map(|s| s.as_str()).collect();
+    match segments.as_slice() {
+        ["charts", code, date] => chart(code, date, &params),
+        ["find", code, date] => find(code, date, &params),
+        ["gain", code, date] => gain(code, date, &params),
+        ["history", code] => history(code, &params),
+        ["daily", date] => daily(date, &params),
+        _ => Err(ApiError::NotFound(format!("No such endpoint: {}", url))),
+    }
+}
+
+pub fn serve(addr: &str) -> Result<(), Box<dyn Error>> {
+    let server = tiny_http::Server::http(addr).map_err(|e| format!("{}: {}", addr, e))?;
+    eprintln!("Listening on http://{}", addr);
+
+    for request in server.incoming_requests() {
+        let result = match request.method() {
+            tiny_http::Method::Get => route(request.url()),
+            _ => Err(ApiError::MethodNotAllowed),
+        };
+        let (status, body) = match result {
+            Ok(value) => (200, value),
+            Err(e) => (e.status(), json!({ "error": e.message() })),
+        };
+        eprintln!("{} {} - {}", request.method(), request.url(), status);
+
+        let response = tiny_http::Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(
+                tiny_http::Header::from_bytes("Content-Type", "application/json")
+                    .expect("valid header"),
+            );
+        if let Err(e) = request.respond(response) {
+            eprintln!("Failed to respond: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_parsing() {
+        let (segments, params) =
+            parse_url("/find/us/2022-06-01?title=as+it%20was&where=rank%3C%3D10");
+        assert_eq!(vec!["find", "us", "2022-06-01"], segments);
+        assert_eq!("as it was", params["title"]);
+        assert_eq!("rank<=10", params["where"]);
+        assert_eq!("100%", percent_decode("100%"));
+    }
+
+    #[test]
+    fn pagination() {
+        let params = parse_url("/?offset=2&limit=2").1;
+        let page = paginate(vec![1, 2, 3, 4, 5], &params).unwrap();
+        assert_eq!((5, vec![3, 4]), (page.total, page.items));
+
+        let params = parse_url("/?limit=x").1;
+        assert_eq!(400, paginate(vec![1], &params).unwrap_err().status());
+    }
+
+    #[test]
+    fn unknown_routes() {
+        assert_eq!(404, route("/nothing").unwrap_err().status());
+        assert_eq!(404, route("/charts/xx/2022-06-01").unwrap_err().status());
+        assert_eq!(400, route("/charts/us/2022-13-01").unwrap_err().status());
+    }
+
+    #[test]
+    fn history_range_is_capped() {
+        let status = |url: &str| route(url).map(|_| 200).unwrap_or_else(|e| e.status());
+        assert_eq!(
+            400,
+            status("/history/us?from=2020-01-01&to=2022-06-01&title=a")
+        );
+        assert_eq!(400, status("/history/us?from=2022-06-01&title=a"));
+        assert_eq!(
+            400,
+            status("/history/us?from=2022-06-02&to=2022-06-01&title=a")
+        );
+    }
+
+    #[test]
+    fn bad_dates() {
+        assert_eq!(400, route("/charts/us/junk").unwrap_err().status());
+        assert_eq!(400, route("/daily/junk?title=a").unwrap_err().status());
+        let url = "/gain/us/2022-06-01?previous=junk";
+        assert_eq!(400, route(url).unwrap_err().status());
+        assert!(match_date("junk").is_err());
+    }
+}
@@ -0,0 +1,404 @@
+use std::{collections::HashMap, error::Error, path::PathBuf};
+
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter,
+    validate::Validator, Context, Editor, Helper,
+};
+
+use super::{
+    add_days, available_dates,
+    filter::Filter,
+    fmt::{add_comma, add_comma_plus, write_report, Format},
+    history::{percent_string, TrackHistory},
+    regions::Regions,
+    validate::verify_code,
+    SpotifyChart, SpotifyEntry, SpotifyGain,
+};
+
+// Days `history` looks back from the current date
+const HISTORY_DAYS: i64 = 14;
+
+const COMMANDS: [&str; 12] = [
+    "use", "date", "next", "prev", "top", "find", "gain", "diff", "history", "regions", "help",
+    "quit",
+];
+
+const HELP: &str = "\
+use <code> [date]   switch region, the latest date by default
+date <date>         switch date
+next, prev          move one day
+top [n]             top n entries, 10 by default
+find <filter>       entries matching a filter, e.g. find harry or find rank<=5
+gain [filter]       changes against the previous day
+diff <date>         entries new, dropped and moved against another date
+history <filter>    a track over the last 14 days
+regions             region codes
+quit                leave the shell";
+
+// Candidates for the word under the cursor: commands first, region codes
+// after `use`
+pub fn complete(line: &str, pos: usize) -> (usize, Vec<String>) {
+    let line = &line[..pos];
+    let start = line.rfind(' ').map_or(0, |i| i + 1);
+    let word = &line[start..];
+
+    let candidates: Vec<String> = if start == 0 {
+        COMMANDS.iter().map(|command| command.to_string()).collect()
+    } else if line.split_whitespace().next() == Some("use") && line[..start].trim() == "use" {
+        Regions::regions_vec()
+            .into_iter()
+            .map(String::from)
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    (
+        start,
+        candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(word))
+            .collect(),
+    )
+}
+
+struct ShellHelper;
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(line, pos))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+// Charts stay loaded for the whole session
+#[derive(Default)]
+struct Session {
+    code: Option<String>,
+    date: Option<String>,
+    charts: HashMap<(String, String), SpotifyChart>,
+}
+
+impl Session {
+    fn chart(&mut self, code: &str, date: &str) -> Result<&SpotifyChart, Box<dyn Error>> {
+        let key = (code.to_string(), date.to_string());
+        if !self.charts.contains_key(&key) {
+            let chart = SpotifyChart::from_code_date(code, date)?;
+            self.charts.insert(key.clone(), chart);
+        }
+        Ok(&self.charts[&key])
+    }
+
+    fn current(&self) -> Result<(String, String), Box<dyn Error>> {
+        match (&self.code, &self.date) {
+            (Some(code), Some(date)) => Ok((code.clone(), date.clone())),
+            _ => Err(From::from("No chart selected, try: use us 2022-06-01")),
+        }
+    }
+
+    fn select(&mut self, code: &str, date: &str) -> Result<(), Box<dyn Error>> {
+        let count = self.chart(code, date)?.chart.len();
+        println!("{} {} - {} entries", code, date, count);
+        self.code = Some(code.to_string());
+        self.date = Some(date.to_string());
+        Ok(())
+    }
+
+    fn print_entries(entries: &[&SpotifyEntry]) -> Result<(), Box<dyn Error>> {
+        write_report(
+            std::io::stdout(),
+            Format::Text,
+            &["Rank", "Title", "Artist", "Streams"],
+            entries,
+            |entry| {
+                vec![
+                    entry.rank.to_string(),
+                    entry.title.clone(),
+                    entry.artist.clone(),
+                    add_comma(entry.streams),
+                ]
+            },
+        )
+    }
+
+    fn print_gains(gains: &[SpotifyGain]) -> Result<(), Box<dyn Error>> {
+        write_report(
+            std::io::stdout(),
+            Format::Text,
+            &[
+                "Rank", "Prev", "+/-", "Title", "Artist", "Streams", "Change", "%",
+            ],
+            gains,
+            |gain| {
+                let new = gain.yesterday_rank == 0;
+                vec![
+                    gain.today_rank.to_string(),
+                    if new {
+                        "-".to_string()
+                    } else {
+                        gain.yesterday_rank.to_string()
+                    },
+                    if new {
+                        "NE".to_string()
+                    } else {
+                        format!("{:+}", gain.rank_diff)
+                    },
+                    gain.title.clone(),
+                    gain.artist.clone(),
+                    add_comma(gain.today_streams),
+                    add_comma_plus(gain.streams_diff),
+                    if new {
+                        "N/A".to_string()
+                    } else {
+                        percent_string(gain.percent_diff)
+                    },
+                ]
+            },
+        )
+    }
+
+    // Gains of the current chart against another date, only for entries
+    // matching the filter
+    fn gains_against(
+        &mut self,
+        other: &str,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<SpotifyGain>, Box<dyn Error>> {
+        let (code, date) = self.current()?;
+        self.chart(&code, other)?;
+        self.chart(&code, &date)?;
+        let chart = &self.charts[&(code.clone(), date)];
+        let other = &self.charts[&(code, other.to_string())];
+
+        Ok(chart
+            .gains(other)
+            .into_iter()
+            .filter(|gain| {
+                filter.is_none_or(|filter| {
+                    chart
+                        .find_exact(&gain.title, &gain.artist)
+                        .is_some_and(|entry| filter.matches(entry))
+                })
+            })
+            .collect())
+    }
+
+    // Returns true when the shell should quit
+    fn run_command(&mut self, line: &str) -> Result<bool, Box<dyn Error>> {
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.trim();
+
+        match command {
+            "" => (),
+            "quit" | "exit" => return Ok(true),
+            "help" => println!("{}", HELP),
+            "regions" => println!(
+                "{}",
+                Regions::regions_vec()
+                    .into_iter()
+                    .map(String::from)
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            ),
+            "use" => {
+                let mut words = args.split_whitespace();
+                let code = words.next().ok_or("Usage: use <code> [date]")?;
+                if !verify_code(code) {
+                    return Err(From::from(format!("Invalid code: \"{}\"", code)));
+                }
+                let date = match words.next() {
+                    Some(date) => date.to_string(),
+                    None => available_dates(code)?
+                        .pop()
+                        .ok_or(format!("No chart data found for {}", code))?,
+                };
+                self.select(code, &date)?;
+            }
+            "date" => {
+                let (code, _) = self.current()?;
+                if args.is_empty() {
+                    return Err(From::from("Usage: date <date>"));
+                }
+                self.select(&code, args)?;
+            }
+            "next" | "prev" => {
+                let (code, date) = self.current()?;
+                let date = add_days(&date, if command == "next" { 1 } else { -1 })?;
+                self.select(&code, &date)?;
+            }
+            "top" => {
+                let n: usize = if args.is_empty() { 10 } else { args.parse()? };
+                let (code, date) = self.current()?;
+                let chart = self.chart(&code, &date)?;
+                Session::print_entries(&chart.chart.iter().take(n).collect::<Vec<_>>())?;
+            }
+            "find" => {
+                let filter = Filter::parse(args)?;
+                let (code, date) = self.current()?;
+                let entries = self.chart(&code, &date)?.find_all_where(&filter);
+                Session::print_entries(&entries.unwrap_or_default())?;
+            }
+            "gain" => {
+                let filter = if args.is_empty() {
+                    None
+                } else {
+                    Some(Filter::parse(args)?)
+                };
+                let (_, date) = self.current()?;
+                let mut gains = self.gains_against(&add_days(&date, -1)?, filter.as_ref())?;
+                if filter.is_none() {
+                    gains.truncate(10);
+                }
+                Session::print_gains(&gains)?;
+            }
+            "diff" => {
+                if args.is_empty() {
+                    return Err(From::from("Usage: diff <date>"));
+                }
+                let (code, date) = self.current()?;
+                let gains = self.gains_against(args, None)?;
+                let (new, mut moved): (Vec<SpotifyGain>, Vec<SpotifyGain>) =
+                    gains.into_iter().partition(|gain| gain.yesterday_rank == 0);
+                moved.sort_by_key(|gain| std::cmp::Reverse(gain.rank_diff.abs()));
+                moved.truncate(10);
+
+                let chart = &self.charts[&(code.clone(), date)];
+                let dropped: Vec<&SpotifyEntry> = self.charts[&(code, args.to_string())]
+                    .chart
+                    .iter()
+                    .filter(|entry| chart.find_exact(&entry.title, &entry.artist).is_none())
+                    .collect();
+
+                println!("New since {}: {}", args, new.len());
+                Session::print_gains(&new)?;
+                println!("\nDropped since {}: {}", args, dropped.len());
+                Session::print_entries(&dropped)?;
+                println!("\nBiggest moves since {}:", args);
+                Session::print_gains(&moved)?;
+            }
+            "history" => {
+                let filter = Filter::parse(args)?;
+                let (code, date) = self.current()?;
+                let mut charts = Vec::new();
+                for offset in (0..HISTORY_DAYS).rev() {
+                    let day = add_days(&date, -offset)?;
+                    if let Ok(chart) = self.chart(&code, &day) {
+                        charts.push(chart.clone());
+                    }
+                }
+                let history = TrackHistory::from_charts(&charts, &filter, false)
+                    .ok_or(format!("No track matching where<{}>", filter))?;
+
+                println!("{} - {}", history.title, history.artist);
+                write_report(
+                    std::io::stdout(),
+                    Format::Text,
+                    &["Date", "Rank", "Streams", "Change", "%"],
+                    &history.points,
+                    |point| {
+                        vec![
+                            point.date.clone(),
+                            if point.rank == 0 {
+                                "-".to_string()
+                            } else {
+                                point.rank.to_string()
+                            },
+                            add_comma(point.streams),
+                            add_comma_plus(point.streams_diff),
+                            percent_string(point.percent_diff),
+                        ]
+                    },
+                )?;
+            }
+            _ => {
+                return Err(From::from(format!(
+                    "Unknown command: \"{}\", try help",
+                    command
+                )))
+            }
+        }
+        Ok(false)
+    }
+}
+
+fn history_file() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".chart_history"))
+}
+
+pub fn shell(code: Option<&str>, date: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let mut editor: Editor<ShellHelper, _> = Editor::new()?;
+    editor.set_helper(Some(ShellHelper));
+    let history = history_file();
+    if let Some(history) = &history {
+        // No history yet on the first run
+        let _ = editor.load_history(history);
+    }
+
+    let mut session = Session::default();
+    if let Some(code) = code {
+        let command = format!("use {} {}", code, date.unwrap_or_default());
+        if let Err(e) = session.run_command(command.trim()) {
+            eprintln!("{}", e);
+        }
+    }
+
+    loop {
+        let prompt = match session.current() {
+            Ok((code, date)) => format!("{} {}> ", code, date),
+            Err(_) => "chart> ".to_string(),
+        };
+        match editor.readline(&prompt) {
+            Ok(line) => {
+                let line = line.trim();
+                if !line.is_empty() {
+                    editor.add_history_entry(line)?;
+                }
+                match session.run_command(line) {
+                    Ok(true) => break,
+                    Ok(false) => (),
+                    Err(e) => eprintln!("{}", e),
+                }
+            }
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(From::from(e)),
+        }
+    }
+
+    if let Some(history) = &history {
+        editor.save_history(history)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn complete_commands_and_codes() {
+        assert_eq!((0, vec!["history".to_string()]), complete("hi", 2));
+        assert_eq!(
+            (4, vec!["global".to_string(), "gb".to_string()]),
+            complete("use g", 5)
+        );
+        assert_eq!((5, Vec::<String>::new()), complete("find g", 6));
+        assert!(complete("use us 20", 9).1.is_empty());
+    }
+}
@@ -0,0 +1,578 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+    fs,
+    path::Path,
+};
+
+use super::{
+    add_days,
+    artists::{Credit, Role},
+    available_codes, available_dates,
+    fmt::{add_comma, add_comma_plus, escape_html, html_row, slug},
+    history::{percent_string, HistoryPoint, TrackHistory},
+    svg::history_svg,
+    validate::verify_code,
+    SpotifyChart, SpotifyGain,
+};
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em auto; max-width: 60em; color: #3e3e40; }
+a { color: #4687d7; text-decoration: none; }
+table { border-collapse: collapse; width: 100%; }
+th, td { padding: 0.2em 0.6em; border-bottom: 1px solid #ddd; text-align: right; }
+th { text-align: center; }
+td.first { text-align: left; white-space: nowrap; }
+td.center { text-align: left; }
+nav { margin-bottom: 1em; }
+";
+
+// Reads the chart of a region code and date
+type Load = dyn Fn(&str, &str) -> Result<SpotifyChart, Box<dyn Error>>;
+
+// Everything known about a track once every region is read
+#[derive(Debug)]
+struct Track {
+    slug: String,
+    title: String,
+    artist: String,
+    credits: Vec<Credit>,
+    // (date, rank, streams) of every charting day, by region code
+    days: BTreeMap<String, Vec<(String, i16, i64)>>,
+}
+
+#[derive(Debug, Default)]
+struct Site {
+    tracks: Vec<Track>,
+    by_song: HashMap<(String, String), usize>,
+    slugs: HashSet<String>,
+    // Region codes and names of every date
+    dates: BTreeMap<String, Vec<(String, String)>>,
+    regions: BTreeMap<String, String>,
+}
+
+// Unique file name out of a name, a number is added when it is taken
+pub fn unique_slug(name: &str, taken: &mut HashSet<String>) -> String {
+    let base = match slug(name) {
+        slug if slug.is_empty() => "page".to_string(),
+        slug => slug,
+    };
+    let mut candidate = base.clone();
+    let mut n = 1;
+    while taken.contains(&candidate) {
+        n += 1;
+        candidate = format!("{}-{}", base, n);
+    }
+    taken.insert(candidate.clone());
+    candidate
+}
+
+// A page `depth` directories below the root
+fn page(title: &str, depth: usize, body: &str) -> String {
+    let root = "../".repeat(depth);
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{title}</title>
+<link rel=\"stylesheet\" href=\"{root}style.css\">
+</head>
+<body>
+<nav><a href=\"{root}index.html\">Dates</a> | <a href=\"{root}artists/index.html\">Artists</a></nav>
+<h1>{title}</h1>
+{body}</body>
+</html>
+",
+        title = escape_html(title),
+        root = root,
+        body = body
+    )
+}
+
+fn write_page(out: &Path, path: &str, html: &str) -> Result<(), Box<dyn Error>> {
+    let path = out.join(path);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", e, dir.display()))?;
+    }
+    fs::write(&path, html).map_err(|e| From::from(format!("{}: {}", e, path.display())))
+}
+
+fn artist_link(credit: &Credit, artist_slugs: &HashMap<String, String>, depth: usize) -> String {
+    match artist_slugs.get(&credit.name.to_lowercase()) {
+        Some(slug) => format!(
+            "<a href=\"{}artists/{}.html\">{}</a>",
+            "../".repeat(depth),
+            slug,
+            escape_html(&credit.name)
+        ),
+        None => escape_html(&credit.name),
+    }
+}
+
+impl Site {
+    fn track(&mut self, title: &str, artist: &str, credits: &[Credit]) -> usize {
+        let key = (title.to_string(), artist.to_string());
+        if let Some(&i) = self.by_song.get(&key) {
+            return i;
+        }
+        let slug = unique_slug(&format!("{} {}", title, artist), &mut self.slugs);
+        self.tracks.push(Track {
+            slug,
+            title: title.to_string(),
+            artist: artist.to_string(),
+            credits: credits.to_vec(),
+            days: BTreeMap::new(),
+        });
+        self.by_song.insert(key, self.tracks.len() - 1);
+        self.tracks.len() - 1
+    }
+
+    // Day pages of a region, compared with the day before when it is there
+    fn region_pages(
+        &mut self,
+        out: &Path,
+        code: &str,
+        dates: &[String],
+        load: &Load,
+    ) -> Result<usize, Box<dyn Error>> {
+        // The day before the first date is only compared with, not rendered
+        let mut previous_chart = match dates.first() {
+            Some(first) => {
+                let previous = add_days(first, -1)?;
+                match load(code, &previous) {
+                    Ok(chart) => chart,
+                    Err(_) => SpotifyChart::empty(code, &previous)?,
+                }
+            }
+            None => return Ok(0),
+        };
+        let mut written = 0;
+        for (i, date) in dates.iter().enumerate() {
+            let chart = match load(code, date) {
+                Ok(chart) => chart,
+                Err(e) => {
+                    eprintln!("{} - {}", code, e);
+                    continue;
+                }
+            };
+            let previous = add_days(date, -1)?;
+            if previous_chart.date_string != previous {
+                previous_chart = SpotifyChart::empty(code, &previous)?;
+            }
+            self.regions.insert(code.to_string(), chart.region.clone());
+            self.dates
+                .entry(date.clone())
+                .or_default()
+                .push((code.to_string(), chart.region.clone()));
+
+            let mut body = String::from("<nav>");
+            if i > 0 {
+                body.push_str(&format!(
+                    "<a href=\"{}.html\">&larr; {}</a> | ",
+                    dates[i - 1],
+                    dates[i - 1]
+                ));
+            }
+            body.push_str(&format!(
+                "<a href=\"../dates/{}.html\">All regions</a>",
+                date
+            ));
+            if let Some(next) = dates.get(i + 1) {
+                body.push_str(&format!(" | <a href=\"{}.html\">{} &rarr;</a>", next, next));
+            }
+            body.push_str("</nav>\n");
+            if previous_chart.chart.is_empty() {
+                body.push_str("<p>Previous day data missing!</p>\n");
+            }
+            body.push_str("<table>\n<tr>\n\t<th>Rank</th>\n\t<th>Track</th>\n\t<th>Streams</th>\n\t<th>Change</th>\n\t<th>%</th>\n</tr>\n");
+            for (gain, entry) in chart.gains(&previous_chart).iter().zip(&chart.chart) {
+                let track = self.track(&entry.title, &entry.artist, &entry.artists);
+                self.tracks[track]
+                    .days
+                    .entry(code.to_string())
+                    .or_default()
+                    .push((date.clone(), entry.rank, entry.streams));
+                // The daily report's region cell holds the track here
+                let cell = format!(
+                    "<a href=\"../tracks/{}.html\">{}</a> - {}",
+                    self.tracks[track].slug,
+                    escape_html(&gain.title),
+                    escape_html(&gain.artist)
+                );
+                body.push_str(html_row(gain, &cell).trim_end());
+                body.push('\n');
+            }
+            body.push_str("</table>\n");
+
+            let title = format!("{} - {}", chart.region, date);
+            write_page(
+                out,
+                &format!("{}/{}.html", code, date),
+                &page(&title, 1, &body),
+            )?;
+            written += 1;
+            previous_chart = chart;
+        }
+        Ok(written)
+    }
+
+    fn track_page(
+        &self,
+        track: &Track,
+        dates: &HashMap<String, Vec<String>>,
+        artist_slugs: &HashMap<String, String>,
+    ) -> Result<String, Box<dyn Error>> {
+        let artists: Vec<String> = track
+            .credits
+            .iter()
+            .map(|credit| artist_link(credit, artist_slugs, 1))
+            .collect();
+        let mut tables = String::new();
+        let mut histories = Vec::new();
+
+        for (code, days) in &track.days {
+            // Every date of the region from the first to the last day on the
+            // chart, off-chart days have rank 0
+            let region_dates = &dates[code];
+            let first = region_dates
+                .iter()
+                .position(|date| *date == days[0].0)
+                .unwrap_or(0);
+            let last = region_dates
+                .iter()
+                .position(|date| *date == days[days.len() - 1].0)
+                .unwrap_or(region_dates.len() - 1);
+            let by_date: HashMap<&str, (i16, i64)> = days
+                .iter()
+                .map(|(date, rank, streams)| (date.as_str(), (*rank, *streams)))
+                .collect();
+
+            let mut points: Vec<HistoryPoint> = Vec::new();
+            for date in &region_dates[first..=last] {
+                let (rank, streams) = by_date.get(date.as_str()).copied().unwrap_or((0, 0));
+                let (streams_diff, percent_diff) = match points.last() {
+                    Some(previous) => {
+                        let gain = SpotifyGain::new(
+                            rank,
+                            previous.rank,
+                            &track.title,
+                            &track.artist,
+                            streams,
+                            previous.streams,
+                        );
+                        (gain.streams_diff, gain.percent_diff)
+                    }
+                    None => (0, f64::NAN),
+                };
+                points.push(HistoryPoint {
+                    date: date.clone(),
+                    rank,
+                    streams,
+                    estimated: false,
+                    streams_diff,
+                    percent_diff,
+                    moving_average: None,
+                    week_over_week: None,
+                    weekday_adjusted: None,
+                });
+            }
+            let history = TrackHistory {
+                title: track.title.clone(),
+                artist: track.artist.clone(),
+                region: self.regions[code].clone(),
+                points,
+            };
+
+            let best = days.iter().map(|day| day.1).min().unwrap_or(0);
+            tables.push_str(&format!(
+                "<h2>{}</h2>\n<p>{} days on the chart, peak #{}, {} streams</p>\n",
+                escape_html(&history.region),
+                days.len(),
+                best,
+                add_comma(history.total_streams())
+            ));
+            tables.push_str("<table>\n<tr>\n\t<th>Date</th>\n\t<th>Rank</th>\n\t<th>Streams</th>\n\t<th>Change</th>\n\t<th>%</th>\n</tr>\n");
+            for point in history.points.iter().rev() {
+                tables.push_str(&format!(
+                    "<tr>\n\t<td class=\"first\"><a href=\"../{}/{}.html\">{}</a></td>\n\t<td>{}</td>\n\t<td>{}</td>\n\t<td>{}</td>\n\t<td>{}</td>\n</tr>\n",
+                    code,
+                    point.date,
+                    point.date,
+                    if point.rank == 0 { "-".to_string() } else { point.rank.to_string() },
+                    add_comma(point.streams),
+                    add_comma_plus(point.streams_diff),
+                    percent_string(point.percent_diff)
+                ));
+            }
+            tables.push_str("</table>\n");
+            histories.push(history);
+        }
+
+        // Every region the track charted in as one line
+        let body = format!(
+            "<p>{}</p>\n{}{}",
+            artists.join(", "),
+            history_svg(&histories, false)?,
+            tables
+        );
+        Ok(page(
+            &format!("{} - {}", track.title, track.artist),
+            1,
+            &body,
+        ))
+    }
+
+    // Credited artists and the tracks they are on, by lowercased name
+    fn artists(&self) -> BTreeMap<String, (String, Vec<(usize, Role)>)> {
+        let mut artists: BTreeMap<String, (String, Vec<(usize, Role)>)> = BTreeMap::new();
+        for (i, track) in self.tracks.iter().enumerate() {
+            for credit in &track.credits {
+                artists
+                    .entry(credit.name.to_lowercase())
+                    .or_insert_with(|| (credit.name.clone(), Vec::new()))
+                    .1
+                    .push((i, credit.role));
+            }
+        }
+        artists
+    }
+
+    fn artist_page(&self, name: &str, tracks: &[(usize, Role)]) -> String {
+        let mut body = String::from("<table>\n<tr>\n\t<th>Track</th>\n\t<th>Role</th>\n\t<th>Regions</th>\n\t<th>Peak</th>\n\t<th>Days</th>\n</tr>\n");
+        for &(i, role) in tracks {
+            let track = &self.tracks[i];
+            // Best rank and the region it was reached in
+            let (peak, region) = track
+                .days
+                .iter()
+                .flat_map(|(code, days)| days.iter().map(move |day| (day.1, code)))
+                .min()
+                .map_or((0, String::new()), |(rank, code)| {
+                    (rank, self.regions[code].clone())
+                });
+            let days: usize = track.days.values().map(Vec::len).sum();
+            body.push_str(&format!(
+                "<tr>\n\t<td class=\"first\"><a href=\"../tracks/{}.html\">{}</a> - {}</td>\n\t<td class=\"center\">{}</td>\n\t<td>{}</td>\n\t<td>#{} ({})</td>\n\t<td>{}</td>\n</tr>\n",
+                track.slug,
+                escape_html(&track.title),
+                escape_html(&track.artist),
+                if role == Role::Lead { "Lead" } else { "Featured" },
+                track.days.len(),
+                peak,
+                escape_html(&region),
+                days
+            ));
+        }
+        body.push_str("</table>\n");
+        page(name, 1, &body)
+    }
+}
+
+// Render the archive as a static site under `out`: a page per region and
+// day, track pages with their history in every region, artist pages and
+// index pages by date
+pub fn site(
+    out: &str,
+    codes: &[String],
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let out = Path::new(out);
+    let codes = if codes.is_empty() {
+        available_codes()?
+    } else {
+        if let Some(code) = codes.iter().find(|code| !verify_code(code)) {
+            return Err(From::from(format!("Invalid code: \"{}\"", code)));
+        }
+        codes.to_vec()
+    };
+
+    let mut region_dates = Vec::new();
+    for code in codes {
+        let dates: Vec<String> = available_dates(&code)?
+            .into_iter()
+            .filter(|date| from.is_none_or(|from| date.as_str() >= from))
+            .filter(|date| to.is_none_or(|to| date.as_str() <= to))
+            .collect();
+        region_dates.push((code, dates));
+    }
+    render(out, &region_dates, &SpotifyChart::from_code_date)
+}
+
+// Write every page of the regions and their dates
+fn render(
+    out: &Path,
+    region_dates: &[(String, Vec<String>)],
+    load: &Load,
+) -> Result<(), Box<dyn Error>> {
+    let mut site = Site::default();
+    let mut day_pages = 0;
+    for (code, dates) in region_dates {
+        day_pages += site.region_pages(out, code, dates, load)?;
+    }
+    if day_pages == 0 {
+        return Err(From::from("No chart data found!"));
+    }
+    let region_dates: HashMap<String, Vec<String>> = region_dates.iter().cloned().collect();
+
+    let artists = site.artists();
+    // The artists index lives next to the artist pages
+    let mut taken = HashSet::from(["index".to_string()]);
+    let artist_slugs: HashMap<String, String> = artists
+        .iter()
+        .map(|(key, (name, _))| (key.clone(), unique_slug(name, &mut taken)))
+        .collect();
+
+    for track in &site.tracks {
+        let html = site.track_page(track, &region_dates, &artist_slugs)?;
+        write_page(out, &format!("tracks/{}.html", track.slug), &html)?;
+    }
+
+    let mut index = String::from("<ul>\n");
+    for (key, (name, tracks)) in &artists {
+        write_page(
+            out,
+            &format!("artists/{}.html", artist_slugs[key]),
+            &site.artist_page(name, tracks),
+        )?;
+        index.push_str(&format!(
+            "<li><a href=\"{}.html\">{}</a> ({})</li>\n",
+            artist_slugs[key],
+            escape_html(name),
+            tracks.len()
+        ));
+    }
+    index.push_str("</ul>\n");
+    write_page(out, "artists/index.html", &page("Artists", 1, &index))?;
+
+    // Index of every date, newest first, and a page per date linking the regions
+    let mut index = String::from("<ul>\n");
+    for (date, regions) in site.dates.iter().rev() {
+        let links: Vec<String> = regions
+            .iter()
+            .map(|(code, region)| {
+                format!(
+                    "<a href=\"{}/{}.html\">{}</a>",
+                    code,
+                    date,
+                    escape_html(region)
+                )
+            })
+            .collect();
+        index.push_str(&format!(
+            "<li><a href=\"dates/{}.html\">{}</a>: {}</li>\n",
+            date,
+            date,
+            links.join(", ")
+        ));
+
+        let mut body = String::from("<ul>\n");
+        for (code, region) in regions {
+            body.push_str(&format!(
+                "<li><a href=\"../{}/{}.html\">{}</a></li>\n",
+                code,
+                date,
+                escape_html(region)
+            ));
+        }
+        body.push_str("</ul>\n");
+        write_page(out, &format!("dates/{}.html", date), &page(date, 1, &body))?;
+    }
+    index.push_str("</ul>\n");
+    write_page(out, "index.html", &page("Spotify charts", 0, &index))?;
+    write_page(out, "style.css", STYLE)?;
+
+    eprintln!(
+        "Site: {} - {} day pages, {} tracks, {} artists, {} dates",
+        out.display(),
+        day_pages,
+        site.tracks.len(),
+        artists.len(),
+        site.dates.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spotify::{fixtures::chart, SpotifyEntry};
+
+    // Relative links of every page under `dir` that point to no file
+    fn broken_links(root: &Path, dir: &Path, broken: &mut Vec<String>) {
+        let href = regex::Regex::new(r#"href="([^"]+)""#).unwrap();
+        for file in fs::read_dir(dir).unwrap() {
+            let path = file.unwrap().path();
+            if path.is_dir() {
+                broken_links(root, &path, broken);
+                continue;
+            }
+            let html = fs::read_to_string(&path).unwrap();
+            for link in href.captures_iter(&html) {
+                if !path.parent().unwrap().join(&link[1]).is_file() {
+                    broken.push(format!(
+                        "{} -> {}",
+                        path.strip_prefix(root).unwrap().display(),
+                        &link[1]
+                    ));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unique_slugs() {
+        let mut taken = HashSet::new();
+        assert_eq!(
+            "as-it-was-harry-styles",
+            unique_slug("As It Was Harry Styles", &mut taken)
+        );
+        assert_eq!(
+            "as-it-was-harry-styles-2",
+            unique_slug("As it was - Harry Styles", &mut taken)
+        );
+        assert_eq!("page", unique_slug("!!!", &mut taken));
+        assert_eq!("빅뱅", unique_slug("빅뱅", &mut taken));
+    }
+
+    #[test]
+    fn render_small_archive() -> Result<(), Box<dyn Error>> {
+        let out = std::env::temp_dir().join(format!("chart-site-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&out);
+        let load = |code: &str, date: &str| -> Result<SpotifyChart, Box<dyn Error>> {
+            let a = SpotifyEntry::new(1, "A", "Index", 1000);
+            let b = SpotifyEntry::new(1, "B (feat. Joji)", "Artist", 1000);
+            let entries = match (code, date) {
+                ("us", "2022-05-01") => vec![a, SpotifyEntry { rank: 2, ..b }],
+                ("us", "2022-05-02") => vec![b, SpotifyEntry { rank: 2, ..a }],
+                ("gb", "2022-05-02") => vec![a],
+                _ => return Err(From::from("missing")),
+            };
+            Ok(chart(code, date, entries))
+        };
+        let dates = |dates: &[&str]| dates.iter().map(|date| date.to_string()).collect();
+        render(
+            &out,
+            &[
+                ("us".to_string(), dates(&["2022-05-01", "2022-05-02"])),
+                ("gb".to_string(), dates(&["2022-05-02"])),
+            ],
+            &load,
+        )?;
+
+        let mut broken = Vec::new();
+        broken_links(&out, &out, &mut broken);
+        assert_eq!(Vec::<String>::new(), broken);
+        // The artist called "Index" does not take the place of the index
+        assert!(out.join("artists/index-2.html").is_file());
+        let index = fs::read_to_string(out.join("artists/index.html"))?;
+        assert!(index.contains("<h1>Artists</h1>"));
+        assert!(index.contains("href=\"joji.html\""));
+        let track = fs::read_to_string(out.join("tracks/a-index.html"))?;
+        assert!(track.contains("<h2>UK</h2>") && track.contains("<h2>US</h2>"));
+
+        fs::remove_dir_all(&out)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+use std::error::Error;
+
+use serde::Serialize;
+
+use super::{
+    filter::Filter,
+    fmt::{add_comma, write_report, Format},
+    SpotifyChart,
+};
+
+impl SpotifyChart {
+    pub fn total_streams(&self) -> i64 {
+        self.chart.iter().map(|entry| entry.streams).sum()
+    }
+
+    // Fraction of the chart's total streams going to the matching entries
+    pub fn streams_share(&self, filter: &Filter) -> f64 {
+        let streams: i64 = self
+            .chart
+            .iter()
+            .filter(|&entry| filter.matches(entry))
+            .map(|entry| entry.streams)
+            .sum();
+        streams as f64 / self.total_streams() as f64
+    }
+
+    // Fraction of the chart's total streams going to the top n ranks
+    pub fn top_share(&self, n: i16) -> f64 {
+        let streams: i64 = self
+            .chart
+            .iter()
+            .filter(|&entry| entry.rank <= n)
+            .map(|entry| entry.streams)
+            .sum();
+        streams as f64 / self.total_streams() as f64
+    }
+
+    // Herfindahl-Hirschman index of the chart, the sum of every entry's
+    // squared percentage share: 10,000 when one song has all the streams,
+    // 50 when 200 songs share them evenly
+    pub fn herfindahl(&self) -> f64 {
+        let total = self.total_streams() as f64;
+        self.chart
+            .iter()
+            .map(|entry| (entry.streams as f64 / total * 100f64).powi(2))
+            .sum()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChartStats {
+    pub date: String,
+    pub region: String,
+    pub total_streams: i64,
+    pub herfindahl: f64,
+    pub top10_share: f64,
+    // Only set when a filter is given
+    pub share: Option<f64>,
+}
+
+impl ChartStats {
+    pub fn from_chart(chart: &SpotifyChart, filter: Option<&Filter>) -> Self {
+        ChartStats {
+            date: chart.date_string.clone(),
+            region: chart.region.clone(),
+            total_streams: chart.total_streams(),
+            herfindahl: chart.herfindahl(),
+            top10_share: chart.top_share(10),
+            share: filter.map(|filter| chart.streams_share(filter)),
+        }
+    }
+}
+
+pub fn stats(
+    code: &str,
+    from: &str,
+    to: &str,
+    filter: Option<&Filter>,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let rows: Vec<ChartStats> = SpotifyChart::from_code_range(code, from, to)?
+        .iter()
+        .map(|chart| ChartStats::from_chart(chart, filter))
+        .collect();
+
+    let mut header = vec!["Date", "Region", "Streams", "HHI", "Top10"];
+    if filter.is_some() {
+        header.push("Share");
+    }
+
+    write_report(std::io::stdout(), format, &header, &rows, |row| {
+        let mut cells = vec![
+            row.date.clone(),
+            row.region.clone(),
+            add_comma(row.total_streams),
+            format!("{:.1}", row.herfindahl),
+            format!("{:.2}%", row.top10_share * 100f64),
+        ];
+        if let Some(share) = row.share {
+            cells.push(format!("{:.2}%", share * 100f64));
+        }
+        cells
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spotify::fixtures::{chart, streams};
+
+    #[test]
+    fn herfindahl_even() {
+        assert!(
+            (chart("us", "2022-06-01", streams(&[100; 200])).herfindahl() - 50f64).abs() < 1e-9
+        );
+        assert!((chart("us", "2022-06-01", streams(&[100])).herfindahl() - 10000f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn shares() -> Result<(), Box<dyn Error>> {
+        let chart = chart("us", "2022-06-01", streams(&[400, 300, 200, 100]));
+        assert!((chart.top_share(2) - 0.7).abs() < 1e-9);
+        assert!((chart.streams_share(&Filter::parse("title:\"song 3\"")?) - 0.1).abs() < 1e-9);
+        Ok(())
+    }
+}
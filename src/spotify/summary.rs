@@ -0,0 +1,273 @@
+use std::error::Error;
+
+use super::{
+    fmt::{add_comma, add_comma_plus},
+    regions::Regions,
+    SpotifyChart,
+};
+
+#[derive(Debug, PartialEq)]
+pub struct ArtistSummary {
+    pub region: String,
+    pub songs: usize,
+    pub best_rank: i16,
+    pub streams: i64,
+    // The previous day and the changes since, None without a previous chart
+    pub previous_songs: Option<usize>,
+    pub previous_streams: Option<i64>,
+    pub songs_diff: Option<i64>,
+    pub streams_diff: Option<i64>,
+    pub percent_diff: Option<f64>,
+}
+
+impl ArtistSummary {
+    pub fn new(
+        region: &str,
+        songs: usize,
+        best_rank: i16,
+        streams: i64,
+        previous: Option<(usize, i64)>,
+    ) -> Self {
+        ArtistSummary {
+            region: region.to_string(),
+            songs,
+            best_rank,
+            streams,
+            previous_songs: previous.map(|(songs, _)| songs),
+            previous_streams: previous.map(|(_, streams)| streams),
+            songs_diff: previous.map(|(previous_songs, _)| songs as i64 - previous_songs as i64),
+            streams_diff: previous.map(|(_, previous_streams)| streams - previous_streams),
+            percent_diff: previous.map(|(_, previous_streams)| {
+                (streams - previous_streams) as f64 / previous_streams as f64
+            }),
+        }
+    }
+
+    // Aggregate every song the artist is credited on. Without a previous chart
+    // there is nothing to compare with. A best rank of 0 means not on the chart.
+    pub fn from_charts(
+        chart: &SpotifyChart,
+        previous_chart: Option<&SpotifyChart>,
+        artist: &str,
+        features: bool,
+    ) -> Self {
+        let today = chart
+            .find_all_by_credit(artist, features)
+            .unwrap_or_default();
+        let yesterday = previous_chart.map(|previous| {
+            let entries = previous
+                .find_all_by_credit(artist, features)
+                .unwrap_or_default();
+            (
+                entries.len(),
+                entries.iter().map(|entry| entry.streams).sum(),
+            )
+        });
+
+        ArtistSummary::new(
+            &chart.region,
+            today.len(),
+            today.iter().map(|entry| entry.rank).min().unwrap_or(0),
+            today.iter().map(|entry| entry.streams).sum(),
+            yesterday,
+        )
+    }
+
+    // Sum of several regions, the best rank is the best across all of them.
+    // Only regions with a previous chart count towards the changes.
+    pub fn total(region: &str, summaries: &[ArtistSummary]) -> Self {
+        let mut total = ArtistSummary::new(
+            region,
+            summaries.iter().map(|summary| summary.songs).sum(),
+            summaries
+                .iter()
+                .map(|summary| summary.best_rank)
+                .filter(|&rank| rank > 0)
+                .min()
+                .unwrap_or(0),
+            summaries.iter().map(|summary| summary.streams).sum(),
+            None,
+        );
+
+        let compared: Vec<&ArtistSummary> = summaries
+            .iter()
+            .filter(|summary| summary.previous_streams.is_some())
+            .collect();
+        if !compared.is_empty() {
+            let previous_streams: i64 = compared
+                .iter()
+                .filter_map(|summary| summary.previous_streams)
+                .sum();
+            let streams_diff: i64 = compared
+                .iter()
+                .filter_map(|summary| summary.streams_diff)
+                .sum();
+            total.previous_songs = Some(
+                compared
+                    .iter()
+                    .filter_map(|summary| summary.previous_songs)
+                    .sum(),
+            );
+            total.previous_streams = Some(previous_streams);
+            total.songs_diff = Some(
+                compared
+                    .iter()
+                    .filter_map(|summary| summary.songs_diff)
+                    .sum(),
+            );
+            total.streams_diff = Some(streams_diff);
+            total.percent_diff = Some(streams_diff as f64 / previous_streams as f64);
+        }
+        total
+    }
+
+    pub fn print(&self) {
+        let best_rank = if self.best_rank == 0 {
+            "-".to_string()
+        } else {
+            format!("#{}", self.best_rank)
+        };
+        let songs_diff = self
+            .songs_diff
+            .map_or("N/A".to_string(), |diff| format!("{:+}", diff));
+        let streams_diff = self.streams_diff.map_or("N/A".to_string(), add_comma_plus);
+        let percent_diff = match (self.previous_streams, self.percent_diff) {
+            (Some(previous_streams), Some(percent_diff)) if previous_streams != 0 => {
+                format!("{:+.2}%", percent_diff * 100f64)
+            }
+            _ => "N/A".to_string(),
+        };
+
+        println!(
+            "{:11} {:>5} {:>4} {:>5} {:>12} {:>12} {:>8}",
+            self.region,
+            self.songs,
+            songs_diff,
+            best_rank,
+            add_comma(self.streams),
+            streams_diff,
+            percent_diff
+        );
+    }
+}
+
+// Artist summary in every region on a date, followed by the total across all
+// tracked markets. The global chart is listed but left out of the total so
+// streams are not counted twice.
+pub fn artist(artist: &str, date: &str, features: bool) -> Result<(), Box<dyn Error>> {
+    let mut global: Option<ArtistSummary> = None;
+    let mut summaries: Vec<ArtistSummary> = Vec::new();
+
+    for region in Regions::regions_vec() {
+        let is_global = region == Regions::GLOBAL;
+        let code = String::from(region);
+
+        let chart = match SpotifyChart::from_code_date(&code, date) {
+            Ok(chart) => chart,
+            Err(_) => {
+                eprintln!("{} - Today[{}] data missing!", code, date);
+                continue;
+            }
+        };
+        let previous_chart = chart.previous_day();
+        if previous_chart.is_err() {
+            eprintln!("{} - Previous day[-] data missing!", code);
+        }
+
+        let summary =
+            ArtistSummary::from_charts(&chart, previous_chart.as_ref().ok(), artist, features);
+        if summary.songs == 0 && summary.previous_songs.unwrap_or(0) == 0 {
+            continue;
+        }
+
+        if is_global {
+            global = Some(summary);
+        } else {
+            summaries.push(summary);
+        }
+    }
+
+    summaries.sort_by_key(|summary| summary.streams);
+    summaries.reverse();
+
+    println!("Artist: artist<\"{}\"> date<{}>", artist, date);
+    println!(
+        "{:11} {:>5} {:>4} {:>5} {:>12} {:>12} {:>8}",
+        "Region", "Songs", "+/-", "Best", "Streams", "Change", "%"
+    );
+    summaries.iter().for_each(ArtistSummary::print);
+    ArtistSummary::total(&format!("Total[{}]", summaries.len()), &summaries).print();
+    if let Some(global) = global {
+        global.print();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spotify::{fixtures::chart, SpotifyEntry};
+
+    #[test]
+    fn from_charts_features() {
+        let today = chart(
+            "us",
+            "2022-06-02",
+            vec![
+                SpotifyEntry::new(1, "As It Was", "Harry Styles", 3000),
+                SpotifyEntry::new(2, "Song (feat. Harry Styles)", "Someone", 2000),
+                SpotifyEntry::new(3, "Late Night Talking", "Harry Styles", 1000),
+            ],
+        );
+        let yesterday = chart(
+            "us",
+            "2022-06-01",
+            vec![SpotifyEntry::new(1, "As It Was", "Harry Styles", 2000)],
+        );
+
+        let leads = ArtistSummary::from_charts(&today, Some(&yesterday), "harry styles", false);
+        assert_eq!(ArtistSummary::new("US", 2, 1, 4000, Some((1, 2000))), leads);
+
+        let all = ArtistSummary::from_charts(&today, None, "harry styles", true);
+        assert_eq!(3, all.songs);
+        assert_eq!(None, all.previous_streams);
+        assert_eq!(None, all.streams_diff);
+    }
+
+    #[test]
+    fn total_best_rank() {
+        let total = ArtistSummary::total(
+            "Total",
+            &[
+                ArtistSummary::new("US", 0, 0, 0, Some((1, 100))),
+                ArtistSummary::new("UK", 2, 5, 500, Some((2, 400))),
+                ArtistSummary::new("Canada", 1, 9, 100, Some((0, 0))),
+            ],
+        );
+        assert_eq!(
+            ArtistSummary::new("Total", 3, 5, 600, Some((3, 500))),
+            total
+        );
+    }
+
+    #[test]
+    fn total_leaves_out_regions_without_previous_day() {
+        let total = ArtistSummary::total(
+            "Total",
+            &[
+                ArtistSummary::new("US", 2, 1, 3000, Some((2, 2000))),
+                ArtistSummary::new("UK", 1, 4, 5000, None),
+            ],
+        );
+        assert_eq!(8000, total.streams);
+        assert_eq!(Some(2000), total.previous_streams);
+        assert_eq!(Some(0), total.songs_diff);
+        assert_eq!(Some(1000), total.streams_diff);
+        assert_eq!(Some(0.5), total.percent_diff);
+
+        let uncompared =
+            ArtistSummary::total("Total", &[ArtistSummary::new("UK", 1, 4, 5000, None)]);
+        assert_eq!(None, uncompared.streams_diff);
+    }
+}
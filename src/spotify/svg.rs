@@ -0,0 +1,328 @@
+use std::{collections::BTreeSet, error::Error, fmt::Write};
+
+use super::{filter::Filter, fmt::escape_html, history::TrackHistory};
+
+const WIDTH: f64 = 800f64;
+const PANEL_HEIGHT: f64 = 240f64;
+const LEFT: f64 = 60f64;
+const RIGHT: f64 = 20f64;
+const TOP: f64 = 30f64;
+const BOTTOM: f64 = 30f64;
+const LEGEND_ROW: f64 = 18f64;
+const COLORS: [&str; 7] = [
+    "#4687d7", "#ba3219", "#83be28", "#e67e22", "#8e44ad", "#1abc9c", "#3e3e40",
+];
+
+// What `history --svg` draws besides the track itself
+#[derive(Debug, Default)]
+pub struct SvgOptions {
+    // File the chart is written to, the HTML report embeds it either way
+    pub out: Option<String>,
+    // Streams as bars instead of a line
+    pub bars: bool,
+    // Other songs in the same region
+    pub compare: Vec<Filter>,
+    // The same song in other regions
+    pub compare_codes: Vec<String>,
+}
+
+// One line of a panel, values by date index. None leaves a gap.
+#[derive(Debug)]
+pub struct Series {
+    pub label: String,
+    pub values: Vec<Option<f64>>,
+}
+
+// 1, 2 or 5 times a power of ten, so about `ticks` ticks fit in `range`
+pub fn nice_step(range: f64, ticks: usize) -> f64 {
+    let rough = (range / ticks as f64).max(1f64);
+    let power = 10f64.powf(rough.log10().floor());
+    match rough / power {
+        f if f <= 1f64 => power,
+        f if f <= 2f64 => 2f64 * power,
+        f if f <= 5f64 => 5f64 * power,
+        _ => 10f64 * power,
+    }
+}
+
+// Axis labels like 950, 12k and 1.2M
+pub fn short_number(value: f64) -> String {
+    if value >= 1e6 {
+        format!("{}M", (value / 1e5).round() / 10f64)
+    } else if value >= 1e3 {
+        format!("{}k", (value / 1e2).round() / 10f64)
+    } else {
+        format!("{}", value.round())
+    }
+}
+
+// One chart with its axes, `top` is where the panel starts in the image
+struct Panel<'a> {
+    title: &'a str,
+    dates: &'a [String],
+    series: &'a [Series],
+    // Rank axis: 1 at the top
+    invert: bool,
+    bars: bool,
+    top: f64,
+}
+
+impl Panel<'_> {
+    fn draw(&self, svg: &mut String) -> Result<(), Box<dyn Error>> {
+        let (left, right) = (LEFT, WIDTH - RIGHT);
+        let (top, bottom) = (self.top + TOP, self.top + PANEL_HEIGHT - BOTTOM);
+        let max = self
+            .series
+            .iter()
+            .flat_map(|series| series.values.iter().flatten())
+            .fold(0f64, |max, &value| max.max(value));
+
+        // Ranks run from 1 down, streams from 0 up
+        let low = if self.invert { 1f64 } else { 0f64 };
+        let step = nice_step(max, 5);
+        let high = ((max / step).ceil() * step).max(low + step);
+        let y = |value: f64| {
+            let share = (value - low) / (high - low);
+            if self.invert {
+                top + share * (bottom - top)
+            } else {
+                bottom - share * (bottom - top)
+            }
+        };
+        let band = (right - left) / self.dates.len().max(1) as f64;
+        let x = |i: usize| left + (i as f64 + 0.5) * band;
+
+        writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" font-weight=\"bold\">{}</text>",
+            left,
+            self.top + 18f64,
+            escape_html(self.title)
+        )?;
+        let mut tick = low;
+        while tick <= high + 1e-9 {
+            writeln!(
+                svg,
+                "<line x1=\"{left}\" x2=\"{right}\" y1=\"{y:.1}\" y2=\"{y:.1}\" stroke=\"#ddd\"/>\n<text x=\"{tx}\" y=\"{ty:.1}\" text-anchor=\"end\" font-size=\"10\">{label}</text>",
+                left = left,
+                right = right,
+                y = y(tick),
+                tx = left - 6f64,
+                ty = y(tick) + 3f64,
+                label = if self.invert {
+                    format!("#{}", tick)
+                } else {
+                    short_number(tick)
+                }
+            )?;
+            // Rank ticks at 1, then on the steps: 1, 10, 20, ...
+            tick = if self.invert && tick == 1f64 && step > 1f64 {
+                step
+            } else {
+                tick + step
+            };
+        }
+
+        // About 8 date labels at most
+        let every = self.dates.len().div_ceil(8).max(1);
+        for (i, date) in self.dates.iter().enumerate().step_by(every) {
+            writeln!(
+                svg,
+                "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\" font-size=\"10\">{}</text>",
+                x(i),
+                bottom + 16f64,
+                escape_html(date)
+            )?;
+        }
+        writeln!(
+            svg,
+            "<line x1=\"{left}\" x2=\"{right}\" y1=\"{bottom}\" y2=\"{bottom}\" stroke=\"#3e3e40\"/>",
+            left = left,
+            right = right,
+            bottom = bottom
+        )?;
+
+        let count = self.series.len().max(1) as f64;
+        for (n, series) in self.series.iter().enumerate() {
+            let color = COLORS[n % COLORS.len()];
+            if self.bars {
+                // Bars of one date side by side, in 80% of the band
+                let width = band * 0.8 / count;
+                for (i, value) in series.values.iter().enumerate() {
+                    if let Some(value) = value {
+                        writeln!(
+                            svg,
+                            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"/>",
+                            x(i) - band * 0.4 + n as f64 * width,
+                            y(*value),
+                            width,
+                            bottom - y(*value),
+                            color
+                        )?;
+                    }
+                }
+                continue;
+            }
+
+            let mut path = String::new();
+            let mut drawing = false;
+            for (i, value) in series.values.iter().enumerate() {
+                match value {
+                    Some(value) => {
+                        let command = if drawing { "L" } else { "M" };
+                        write!(path, "{}{:.1},{:.1} ", command, x(i), y(*value))?;
+                        drawing = true;
+                    }
+                    None => drawing = false,
+                }
+            }
+            writeln!(
+                svg,
+                "<path d=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>",
+                path.trim_end(),
+                color
+            )?;
+            // Dots, so days between two gaps still show
+            for (i, value) in series.values.iter().enumerate() {
+                if let Some(value) = value {
+                    writeln!(
+                        svg,
+                        "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"2\" fill=\"{}\"/>",
+                        x(i),
+                        y(*value),
+                        color
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Rank and streams of one or more histories as one SVG image, dates are the
+// union of all histories
+pub fn history_svg(histories: &[TrackHistory], bars: bool) -> Result<String, Box<dyn Error>> {
+    let dates: Vec<String> = histories
+        .iter()
+        .flat_map(|history| history.points.iter().map(|point| point.date.clone()))
+        .collect::<BTreeSet<String>>()
+        .into_iter()
+        .collect();
+    let series = |value: &dyn Fn(i16, i64, bool) -> Option<f64>| -> Vec<Series> {
+        histories
+            .iter()
+            .map(|history| Series {
+                label: format!(
+                    "{} - {} ({})",
+                    history.title, history.artist, history.region
+                ),
+                values: dates
+                    .iter()
+                    .map(|date| {
+                        history
+                            .points
+                            .iter()
+                            .find(|point| point.date == *date)
+                            .and_then(|point| value(point.rank, point.streams, point.estimated))
+                    })
+                    .collect(),
+            })
+            .collect()
+    };
+    // Off-chart days are gaps, unless their streams are estimated
+    let ranks = series(&|rank, _, _| (rank > 0).then_some(rank as f64));
+    let streams =
+        series(&|rank, streams, estimated| (rank > 0 || estimated).then_some(streams as f64));
+
+    let legend = if histories.len() > 1 {
+        histories.len()
+    } else {
+        0
+    };
+    let height = 2f64 * PANEL_HEIGHT + legend as f64 * LEGEND_ROW + 10f64;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" font-family=\"sans-serif\" font-size=\"12\">\n<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n",
+        WIDTH, height, WIDTH, height
+    );
+    let title = match histories {
+        [history] => format!("{} - {}", history.title, history.artist),
+        _ => String::new(),
+    };
+    Panel {
+        title: format!("Rank {}", title).trim_end(),
+        dates: &dates,
+        series: &ranks,
+        invert: true,
+        bars: false,
+        top: 0f64,
+    }
+    .draw(&mut svg)?;
+    Panel {
+        title: "Streams",
+        dates: &dates,
+        series: &streams,
+        invert: false,
+        bars,
+        top: PANEL_HEIGHT,
+    }
+    .draw(&mut svg)?;
+
+    for (n, series) in ranks.iter().enumerate().take(legend) {
+        let y = 2f64 * PANEL_HEIGHT + n as f64 * LEGEND_ROW;
+        writeln!(
+            svg,
+            "<rect x=\"{}\" y=\"{}\" width=\"12\" height=\"12\" fill=\"{}\"/>\n<text x=\"{}\" y=\"{}\">{}</text>",
+            LEFT,
+            y,
+            COLORS[n % COLORS.len()],
+            LEFT + 18f64,
+            y + 11f64,
+            escape_html(&series.label)
+        )?;
+    }
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spotify::fixtures::history;
+
+    #[test]
+    fn steps_and_labels() {
+        assert_eq!(1f64, nice_step(4f64, 5));
+        assert_eq!(20f64, nice_step(87f64, 5));
+        assert_eq!(500000f64, nice_step(2_300_000f64, 5));
+        assert_eq!("950", short_number(950f64));
+        assert_eq!("12k", short_number(12_000f64));
+        assert_eq!("1.2M", short_number(1_234_567f64));
+    }
+
+    #[test]
+    fn rank_axis_inverted_with_gaps() -> Result<(), Box<dyn Error>> {
+        let svg = history_svg(&[history("US", &[(1, 900), (0, 0), (10, 500)])], false)?;
+        // Two dots in the rank panel: rank 1 above rank 10, nothing off the chart
+        let dots: Vec<f64> = svg
+            .lines()
+            .filter(|line| line.starts_with("<circle"))
+            .map(|line| {
+                let cy = line.split("cy=\"").nth(1).unwrap();
+                cy[..cy.find('"').unwrap()].parse().unwrap()
+            })
+            .collect();
+        assert_eq!(4, dots.len());
+        assert!(dots[0] < dots[1]);
+        assert!(svg.contains("<path d=\"M"));
+        assert!(!svg.contains("(US)"));
+
+        let svg = history_svg(
+            &[history("US", &[(1, 900)]), history("UK", &[(3, 400)])],
+            true,
+        )?;
+        // Two bars and two legend keys
+        assert_eq!(4, svg.matches("<rect x=").count());
+        assert!(svg.contains("As It Was - Harry Styles (UK)"));
+        Ok(())
+    }
+}
@@ -0,0 +1,465 @@
+use std::{collections::HashMap, error::Error};
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Cell, Paragraph, Row, Sparkline, Table, TableState},
+    DefaultTerminal, Frame,
+};
+
+use super::{
+    add_days, available_codes, available_dates,
+    fmt::{add_comma, add_comma_plus},
+    history::percent_string,
+    SpotifyChart, SpotifyGain,
+};
+
+// Days shown in the history panel
+const HISTORY_DAYS: usize = 30;
+
+// Rank movement of a gain, the way the charts show it
+pub fn movement(gain: &SpotifyGain) -> (String, Color) {
+    if gain.yesterday_rank == 0 {
+        ("NEW".to_string(), Color::Blue)
+    } else if gain.rank_diff > 0 {
+        (format!("▲{}", gain.rank_diff), Color::Green)
+    } else if gain.rank_diff < 0 {
+        (format!("▼{}", -gain.rank_diff), Color::Red)
+    } else {
+        ("=".to_string(), Color::Gray)
+    }
+}
+
+struct App {
+    codes: Vec<String>,
+    code: usize,
+    dates: Vec<String>,
+    date: usize,
+    // Charts loaded so far, None when the file is missing or broken
+    charts: HashMap<(String, String), Option<SpotifyChart>>,
+    gains: Vec<SpotifyGain>,
+    // False when the previous day's file is missing and nothing is compared
+    compared: bool,
+    table: TableState,
+    search: String,
+    searching: bool,
+    history: Option<Vec<u64>>,
+    status: String,
+}
+
+impl App {
+    fn new(code: Option<&str>, date: Option<&str>) -> Result<Self, Box<dyn Error>> {
+        let codes = available_codes()?;
+        if codes.is_empty() {
+            return Err(From::from("No chart data found!"));
+        }
+        let code = match code {
+            Some(code) => codes
+                .iter()
+                .position(|c| c == code)
+                .ok_or(format!("No chart data found for {}", code))?,
+            None => 0,
+        };
+
+        let mut app = App {
+            codes,
+            code,
+            dates: Vec::new(),
+            date: 0,
+            charts: HashMap::new(),
+            gains: Vec::new(),
+            compared: false,
+            table: TableState::default(),
+            search: String::new(),
+            searching: false,
+            history: None,
+            status: String::new(),
+        };
+        app.switch_region(date)?;
+        Ok(app)
+    }
+
+    fn code(&self) -> &str {
+        &self.codes[self.code]
+    }
+
+    fn date(&self) -> &str {
+        &self.dates[self.date]
+    }
+
+    fn load(&mut self, date: &str) -> Option<&SpotifyChart> {
+        let key = (self.code().to_string(), date.to_string());
+        self.charts
+            .entry(key)
+            .or_insert_with_key(|(code, date)| SpotifyChart::from_code_date(code, date).ok())
+            .as_ref()
+    }
+
+    // Keep the date when the new region has it, otherwise the closest earlier one
+    fn switch_region(&mut self, date: Option<&str>) -> Result<(), Box<dyn Error>> {
+        let current = date
+            .map(|date| date.to_string())
+            .or_else(|| self.dates.get(self.date).cloned());
+        let dates = available_dates(self.code())?;
+        if dates.is_empty() {
+            return Err(From::from(format!(
+                "No chart data found for {}",
+                self.code()
+            )));
+        }
+        self.dates = dates;
+        self.date = match current {
+            Some(current) => self
+                .dates
+                .iter()
+                .rposition(|date| *date <= current)
+                .unwrap_or(0),
+            None => self.dates.len() - 1,
+        };
+        self.refresh();
+        Ok(())
+    }
+
+    // Move `step` regions on, modulo the region count, skipping regions
+    // without chart files
+    fn step_region(&mut self, step: usize) {
+        let start = self.code;
+        for i in 1..self.codes.len() {
+            self.code = (start + i * step) % self.codes.len();
+            if self.switch_region(None).is_ok() {
+                return;
+            }
+        }
+        self.code = start;
+        self.status = "No other region has chart data!".to_string();
+    }
+
+    // Rebuild the table for the current region, date and search. The chart is
+    // compared with the calendar day before, not the previous file.
+    fn refresh(&mut self) {
+        let date = self.date().to_string();
+        let previous = add_days(&date, -1).ok();
+        if let Some(previous) = &previous {
+            self.load(previous);
+        }
+        self.load(&date);
+
+        let code = self.code().to_string();
+        let chart = self.charts[&(code.clone(), date.clone())].as_ref();
+        let previous_chart = previous
+            .as_ref()
+            .and_then(|previous| self.charts[&(code, previous.clone())].as_ref());
+        self.compared = previous_chart.is_some();
+
+        self.gains = match chart {
+            Some(chart) => {
+                let gains = match previous_chart {
+                    Some(previous_chart) => chart.gains(previous_chart),
+                    // Listed without a comparison, the movement shows as N/A
+                    None => SpotifyChart::empty(&chart.code, &chart.date_string)
+                        .map(|empty| chart.gains(&empty))
+                        .unwrap_or_default(),
+                };
+                if self.search.is_empty() {
+                    gains
+                } else {
+                    let found = chart
+                        .find_all(None, None, Some(&self.search))
+                        .unwrap_or_default();
+                    gains
+                        .into_iter()
+                        .filter(|gain| {
+                            found.iter().any(|entry| {
+                                entry.title == gain.title && entry.artist == gain.artist
+                            })
+                        })
+                        .collect()
+                }
+            }
+            None => Vec::new(),
+        };
+        self.status = match (chart.is_some(), self.search.is_empty()) {
+            (false, _) => format!("{} - {} data missing!", self.code(), date),
+            (true, true) => String::new(),
+            (true, false) => format!("{} results for \"{}\"", self.gains.len(), self.search),
+        };
+        if chart.is_some() && !self.compared {
+            let missing = format!(
+                "{} - Previous day[{}] data missing!",
+                self.code(),
+                previous.unwrap_or_default()
+            );
+            self.status = if self.status.is_empty() {
+                missing
+            } else {
+                format!("{}  {}", missing, self.status)
+            };
+        }
+
+        let selected = self.table.selected().unwrap_or(0);
+        self.table
+            .select((!self.gains.is_empty()).then(|| selected.min(self.gains.len() - 1)));
+        if self.history.is_some() {
+            self.load_history();
+        }
+    }
+
+    // Streams of the selected track over the last days, 0 when off the chart
+    fn load_history(&mut self) {
+        let gain = match self.table.selected().and_then(|i| self.gains.get(i)) {
+            Some(gain) => (gain.title.clone(), gain.artist.clone()),
+            None => {
+                self.history = None;
+                return;
+            }
+        };
+        let dates: Vec<String> =
+            self.dates[self.date.saturating_sub(HISTORY_DAYS - 1)..=self.date].to_vec();
+        let streams = dates
+            .iter()
+            .map(|date| {
+                self.load(date)
+                    .and_then(|chart| chart.find_exact(&gain.0, &gain.1))
+                    .map_or(0, |entry| entry.streams as u64)
+            })
+            .collect();
+        self.history = Some(streams);
+    }
+
+    fn select(&mut self, offset: i64) {
+        if self.gains.is_empty() {
+            return;
+        }
+        let selected = self.table.selected().unwrap_or(0) as i64 + offset;
+        self.table
+            .select(Some(selected.clamp(0, self.gains.len() as i64 - 1) as usize));
+        if self.history.is_some() {
+            self.load_history();
+        }
+    }
+
+    // Returns true when the app should quit
+    fn handle_key(&mut self, key: KeyEvent) -> Result<bool, Box<dyn Error>> {
+        if self.searching {
+            match key.code {
+                KeyCode::Enter => self.searching = false,
+                KeyCode::Esc => {
+                    self.searching = false;
+                    self.search.clear();
+                }
+                KeyCode::Backspace => {
+                    self.search.pop();
+                }
+                KeyCode::Char(c) => self.search.push(c),
+                _ => return Ok(false),
+            }
+            self.refresh();
+            return Ok(false);
+        }
+
+        match key.code {
+            KeyCode::Char('q') => return Ok(true),
+            KeyCode::Esc if !self.search.is_empty() => {
+                self.search.clear();
+                self.refresh();
+            }
+            KeyCode::Esc => return Ok(true),
+            KeyCode::Left if self.date > 0 => {
+                self.date -= 1;
+                self.refresh();
+            }
+            KeyCode::Right if self.date + 1 < self.dates.len() => {
+                self.date += 1;
+                self.refresh();
+            }
+            KeyCode::Up => self.step_region(self.codes.len() - 1),
+            KeyCode::Down => self.step_region(1),
+            KeyCode::Char('k') => self.select(-1),
+            KeyCode::Char('j') => self.select(1),
+            KeyCode::PageUp => self.select(-20),
+            KeyCode::PageDown => self.select(20),
+            KeyCode::Home => self.select(-(self.gains.len() as i64)),
+            KeyCode::End => self.select(self.gains.len() as i64),
+            KeyCode::Char('/') => self.searching = true,
+            KeyCode::Enter => {
+                if self.history.is_some() {
+                    self.history = None;
+                } else {
+                    self.load_history();
+                }
+            }
+            _ => (),
+        }
+        Ok(false)
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, footer] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+        let [table_area, panel_area] = Layout::horizontal(if self.history.is_some() {
+            [Constraint::Percentage(65), Constraint::Percentage(35)]
+        } else {
+            [Constraint::Percentage(100), Constraint::Percentage(0)]
+        })
+        .areas(main);
+
+        let rows = self.gains.iter().map(|gain| {
+            let (arrow, color) = if self.compared {
+                movement(gain)
+            } else {
+                ("N/A".to_string(), Color::Gray)
+            };
+            Row::new(vec![
+                Cell::from(gain.today_rank.to_string()),
+                Cell::from(arrow).style(Style::default().fg(color)),
+                Cell::from(gain.title.clone()),
+                Cell::from(gain.artist.clone()),
+                Cell::from(Line::from(add_comma(gain.today_streams)).right_aligned()),
+                Cell::from(
+                    Line::from(if gain.yesterday_rank == 0 {
+                        "N/A".to_string()
+                    } else {
+                        percent_string(gain.percent_diff)
+                    })
+                    .right_aligned(),
+                ),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(4),
+                Constraint::Length(5),
+                Constraint::Fill(3),
+                Constraint::Fill(2),
+                Constraint::Length(11),
+                Constraint::Length(9),
+            ],
+        )
+        .header(
+            Row::new(vec!["Rank", "+/-", "Title", "Artist", "Streams", "%"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            " {} - {} ",
+            self.code(),
+            self.date()
+        )));
+        frame.render_stateful_widget(table, table_area, &mut self.table);
+
+        if let Some(history) = &self.history {
+            let gain = self.table.selected().and_then(|i| self.gains.get(i));
+            let title = gain.map_or(String::new(), |gain| format!(" {} ", gain.title));
+            let [info, spark] =
+                Layout::vertical([Constraint::Length(5), Constraint::Min(3)]).areas(panel_area);
+            let lines = match gain {
+                Some(gain) => vec![
+                    Line::from(gain.artist.clone()),
+                    Line::from(format!("Streams: {}", add_comma(gain.today_streams))),
+                    Line::from(format!("Change:  {}", add_comma_plus(gain.streams_diff))),
+                ],
+                None => Vec::new(),
+            };
+            frame.render_widget(
+                Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title)),
+                info,
+            );
+            frame.render_widget(
+                Sparkline::default()
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title(format!(" Last {} days ", history.len())),
+                    )
+                    .data(history)
+                    .style(Style::default().fg(Color::Cyan)),
+                spark,
+            );
+        }
+
+        let footer_text = if self.searching {
+            format!("/{}", self.search)
+        } else if !self.status.is_empty() {
+            self.status.clone()
+        } else {
+            "←/→ date  ↑/↓ region  j/k select  / search  enter history  q quit".to_string()
+        };
+        frame.render_widget(Paragraph::new(footer_text), footer);
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), Box<dyn Error>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && self.handle_key(key)? {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+pub fn tui(code: Option<&str>, date: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let mut app = App::new(code, date)?;
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spotify::fixtures::{chart, titles};
+
+    // An app over charts already in memory, dates without a chart here have
+    // no file either
+    fn app_of(charts: &[(&str, &[&str])]) -> App {
+        App {
+            codes: vec!["us".to_string()],
+            code: 0,
+            dates: charts.iter().map(|(date, _)| date.to_string()).collect(),
+            date: charts.len() - 1,
+            charts: charts
+                .iter()
+                .map(|&(date, songs)| {
+                    let key = ("us".to_string(), date.to_string());
+                    (key, Some(chart("us", date, titles(songs))))
+                })
+                .collect(),
+            gains: Vec::new(),
+            compared: false,
+            table: TableState::default(),
+            search: String::new(),
+            searching: false,
+            history: None,
+            status: String::new(),
+        }
+    }
+
+    #[test]
+    fn compare_with_calendar_day() {
+        let mut app = app_of(&[("2001-01-01", &["A", "B"]), ("2001-01-02", &["B", "A"])]);
+        app.refresh();
+        assert!(app.compared);
+        assert_eq!(1, app.gains[0].rank_diff);
+
+        // 2001-01-02 is missing, the previous file is not the previous day
+        let mut app = app_of(&[("2001-01-01", &["A", "B"]), ("2001-01-03", &["B", "A"])]);
+        app.refresh();
+        assert!(!app.compared);
+        assert_eq!("us - Previous day[2001-01-02] data missing!", app.status);
+    }
+
+    #[test]
+    fn movement_arrows() {
+        let gain = |today, yesterday| SpotifyGain::new(today, yesterday, "A", "B", 10, 10);
+        assert_eq!("NEW", movement(&gain(5, 0)).0);
+        assert_eq!("▲3", movement(&gain(2, 5)).0);
+        assert_eq!("▼1", movement(&gain(6, 5)).0);
+        assert_eq!("=", movement(&gain(5, 5)).0);
+    }
+}
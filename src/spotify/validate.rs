@@ -38,7 +38,9 @@ pub fn match_code(path_name: &str) -> Result<String, Box<dyn Error>> {
 
 pub fn match_date(date: &str) -> Result<Option<Date>, Box<dyn Error>> {
     let re = Regex::new(r"(\d{4})-(\d{2})-(\d{2})").unwrap();
-    let caps = re.captures(&date).unwrap();
+    let caps = re
+        .captures(date)
+        .ok_or_else(|| format!("Invalid date string: {}", date))?;
 
     let year = caps[1].parse::<i32>()?;
     let month: u8 = caps[2].parse::<u8>()?;
@@ -51,10 +53,7 @@ pub fn match_date(date: &str) -> Result<Option<Date>, Box<dyn Error>> {
 
 pub fn verify_code(code: &str) -> bool {
     // Anything that isn't NOTVALID will be true, else false
-    match Regions::from(code) {
-        Regions::NOTVALID => false,
-        _ => true,
-    }
+    !matches!(Regions::from(code), Regions::NOTVALID)
 }
 
 pub fn verify_date(date: &str) -> bool {
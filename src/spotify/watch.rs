@@ -0,0 +1,310 @@
+use std::{
+    collections::BTreeSet,
+    error::Error,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+use super::{
+    daily_gains, data_dir,
+    filter::Filter,
+    fmt::{add_comma, add_comma_plus, escape_html, html_row, write_report, Format},
+    history::percent_string,
+    validate::{verify_code, verify_date},
+    SpotifyChart, SpotifyGain,
+};
+
+// How long to wait for more events before running the reports, so a file
+// written in several steps is only picked up once
+const SETTLE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Report {
+    // Biggest streams increases of the region
+    Gainers,
+    // Gains of the tracked songs in the region
+    Tracked,
+    // Tracked songs in every region as an HTML page
+    Daily,
+}
+
+#[derive(Debug)]
+pub struct WatchOptions {
+    pub reports: Vec<Report>,
+    pub tracks: Vec<Filter>,
+    pub out: PathBuf,
+    pub format: Format,
+    pub limit: usize,
+}
+
+// Region code and date of a chart file path, `{code}/{date}.csv`
+pub fn chart_file(path: &Path) -> Option<(String, String)> {
+    if path.extension()? != "csv" {
+        return None;
+    }
+    let date = path.file_stem()?.to_str()?;
+    let code = path.parent()?.file_name()?.to_str()?;
+    if verify_code(code) && verify_date(date) {
+        Some((code.to_string(), date.to_string()))
+    } else {
+        None
+    }
+}
+
+fn extension(format: Format) -> &'static str {
+    match format {
+        Format::Text => "txt",
+        Format::Csv => "csv",
+        Format::Json => "json",
+        Format::Html => "html",
+    }
+}
+
+fn write_gains(path: &Path, format: Format, gains: &[SpotifyGain]) -> Result<(), Box<dyn Error>> {
+    let file = File::create(path).map_err(|e| format!("{}: {}", e, path.display()))?;
+    write_report(
+        BufWriter::new(file),
+        format,
+        &["Rank", "+/-", "Title", "Artist", "Streams", "Change", "%"],
+        gains,
+        |gain| {
+            let new = gain.yesterday_rank == 0;
+            vec![
+                gain.today_rank.to_string(),
+                if new {
+                    "NE".to_string()
+                } else {
+                    format!("{:+}", gain.rank_diff)
+                },
+                gain.title.clone(),
+                gain.artist.clone(),
+                add_comma(gain.today_streams),
+                add_comma_plus(gain.streams_diff),
+                if new {
+                    "N/A".to_string()
+                } else {
+                    percent_string(gain.percent_diff)
+                },
+            ]
+        },
+    )
+}
+
+// Run the reports for a new chart file, returns the files written. The
+// gainers and tracked reports compare with the previous day and are skipped
+// without it.
+pub fn run_reports(
+    code: &str,
+    date: &str,
+    options: &WatchOptions,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let chart = SpotifyChart::from_code_date(code, date)?;
+    let gains = match chart.previous_day() {
+        Ok(previous_chart) => Some(chart.gains(&previous_chart)),
+        Err(_) => {
+            eprintln!("{} - Previous day[-] data missing!", code);
+            None
+        }
+    };
+
+    let dir = options.out.join(code);
+    fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", e, dir.display()))?;
+    let mut written = Vec::new();
+
+    for report in &options.reports {
+        match (report, &gains) {
+            (Report::Gainers | Report::Tracked, None) => {
+                eprintln!(
+                    "{} - No previous day, skipping the {:?} report",
+                    code, report
+                );
+            }
+            (Report::Gainers, Some(gains)) => {
+                let mut gainers: Vec<SpotifyGain> = gains
+                    .iter()
+                    .filter(|gain| gain.yesterday_rank != 0)
+                    .cloned()
+                    .collect();
+                gainers.sort_by_key(|gain| std::cmp::Reverse(gain.streams_diff));
+                gainers.truncate(options.limit);
+
+                let path = dir.join(format!("{}-gainers.{}", date, extension(options.format)));
+                write_gains(&path, options.format, &gainers)?;
+                written.push(path);
+            }
+            (Report::Tracked, _) if options.tracks.is_empty() => {
+                eprintln!("No tracked songs given, skipping the tracked report");
+            }
+            (Report::Tracked, Some(gains)) => {
+                let tracked: Vec<SpotifyGain> = gains
+                    .iter()
+                    .filter(|gain| {
+                        chart
+                            .find_exact(&gain.title, &gain.artist)
+                            .is_some_and(|entry| options.tracks.iter().any(|f| f.matches(entry)))
+                    })
+                    .cloned()
+                    .collect();
+
+                let path = dir.join(format!("{}-tracked.{}", date, extension(options.format)));
+                write_gains(&path, options.format, &tracked)?;
+                written.push(path);
+            }
+            (Report::Daily, _) if options.tracks.is_empty() => {
+                eprintln!("No tracked songs given, skipping the daily report");
+            }
+            (Report::Daily, _) => {
+                // Every region of the date goes into one page, so it is
+                // written again as each region's file comes in
+                let path = options.out.join(format!("{}-daily.html", date));
+                let file = File::create(&path).map_err(|e| format!("{}: {}", e, path.display()))?;
+                let mut w = BufWriter::new(file);
+                for filter in &options.tracks {
+                    writeln!(w, "<h2>{}</h2>\n<table>", escape_html(&filter.to_string()))?;
+                    for gain in daily_gains(date, filter)? {
+                        writeln!(w, "{}", html_row(&gain.gain, &gain.region))?;
+                    }
+                    writeln!(w, "</table>")?;
+                }
+                w.flush()?;
+                written.push(path);
+            }
+        }
+    }
+
+    Ok(written)
+}
+
+fn collect(event: notify::Result<Event>, pending: &mut BTreeSet<(String, String)>) {
+    match event {
+        Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+            pending.extend(event.paths.iter().filter_map(|path| chart_file(path)));
+        }
+        Ok(_) => (),
+        Err(e) => eprintln!("Watch error: {}", e),
+    }
+}
+
+// Watch the data directory and run the reports for every chart file that is
+// added or changed
+pub fn watch(options: WatchOptions) -> Result<(), Box<dyn Error>> {
+    let dir = data_dir();
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(&dir, RecursiveMode::Recursive)?;
+    eprintln!(
+        "Watching {} - reports go to {}",
+        dir.display(),
+        options.out.display()
+    );
+
+    while let Ok(event) = rx.recv() {
+        let mut pending = BTreeSet::new();
+        collect(event, &mut pending);
+        while let Ok(event) = rx.recv_timeout(SETTLE) {
+            collect(event, &mut pending);
+        }
+
+        for (code, date) in pending {
+            match run_reports(&code, &date, &options) {
+                Ok(written) => {
+                    for path in written {
+                        println!("{} {} -> {}", code, date, path.display());
+                    }
+                }
+                Err(e) => eprintln!("{} {} - {}", code, date, e),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_chart(dir: &Path, code: &str, date: &str, songs: &[(&str, i64)]) {
+        let dir = dir.join(code);
+        fs::create_dir_all(&dir).unwrap();
+        let lines: Vec<String> = songs
+            .iter()
+            .enumerate()
+            .map(|(i, (title, streams))| {
+                format!("{}*{}*Artist*{}\n", i + 1, title, add_comma(*streams))
+            })
+            .collect();
+        fs::write(dir.join(format!("{}.csv", date)), lines.concat()).unwrap();
+    }
+
+    // The only test reading charts through CHARTSCAN_DATA, the others build
+    // their charts in memory
+    #[test]
+    fn reports_of_new_files() -> Result<(), Box<dyn Error>> {
+        let root = std::env::temp_dir().join(format!("chart-watch-{}", std::process::id()));
+        let data = root.join("data");
+        write_chart(&data, "us", "2022-06-01", &[("A", 2000), ("B", 1000)]);
+        write_chart(&data, "us", "2022-06-02", &[("B", 3000), ("A", 2500)]);
+        write_chart(&data, "gb", "2022-06-02", &[("A", 500)]);
+        std::env::set_var("CHARTSCAN_DATA", &data);
+
+        let options = WatchOptions {
+            reports: vec![Report::Gainers, Report::Tracked, Report::Daily],
+            tracks: vec![Filter::parse("title:A")?],
+            out: root.join("out"),
+            format: Format::Csv,
+            limit: 10,
+        };
+        let written = run_reports("us", "2022-06-02", &options)?;
+        let names: Vec<String> = written
+            .iter()
+            .map(|path| {
+                path.strip_prefix(&options.out)
+                    .unwrap()
+                    .display()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                "us/2022-06-02-gainers.csv",
+                "us/2022-06-02-tracked.csv",
+                "2022-06-02-daily.html",
+            ],
+            names
+        );
+        let gainers = fs::read_to_string(&written[0])?;
+        assert!(gainers.contains("1,+1,B,Artist,\"3,000\",\"+2,000\",+200.00%"));
+        let tracked = fs::read_to_string(&written[1])?;
+        assert!(tracked.contains("2,-1,A,Artist,\"2,500\",+500,+25.00%"));
+        let daily = fs::read_to_string(&written[2])?;
+        assert!(daily.contains("US") && daily.contains("2,500"));
+
+        // The UK has no previous day, only the daily page is written
+        let written = run_reports("gb", "2022-06-02", &options)?;
+        assert_eq!(vec![options.out.join("2022-06-02-daily.html")], written);
+        assert!(!fs::read_to_string(&written[0])?.contains("UK"));
+
+        fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn chart_file_paths() {
+        assert_eq!(
+            Some(("us".to_string(), "2022-06-01".to_string())),
+            chart_file(Path::new("/data/SpotifyData/us/2022-06-01.csv"))
+        );
+        assert_eq!(None, chart_file(Path::new("/data/us/2022-06-01.csv.tmp")));
+        assert_eq!(None, chart_file(Path::new("/data/out/2022-06-01.csv")));
+        assert_eq!(
+            None,
+            chart_file(Path::new("/data/us/2022-06-01-gainers.csv"))
+        );
+    }
+}
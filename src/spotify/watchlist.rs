@@ -0,0 +1,151 @@
+use std::{error::Error, path::Path};
+
+use serde::Deserialize;
+
+use super::{filter::Filter, validate::verify_code};
+
+// Songs and regions the team follows, read from a TOML or JSON file:
+//
+//     regions = ["us", "gb"]
+//
+//     [[track]]
+//     title = "As It Was"
+//     artist = "Harry Styles"
+//
+//     [[track]]
+//     artist = "Joji"
+//
+//     [[track]]
+//     where = 'lead:"BTS" and not title:/remix/i'
+//
+// Without `regions` every region is in the digest. An entry with only an
+// artist follows every song of the artist.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Watchlist {
+    #[serde(default)]
+    pub regions: Vec<String>,
+    #[serde(rename = "track", alias = "tracks", default)]
+    pub tracks: Vec<WatchedTrack>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchedTrack {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    // Spotify URI, kept as a label only since the chart files carry no URIs
+    pub uri: Option<String>,
+    #[serde(rename = "where")]
+    pub where_expr: Option<String>,
+}
+
+impl WatchedTrack {
+    pub fn filter(&self) -> Result<Filter, Box<dyn Error>> {
+        Filter::from_options(
+            self.title.as_deref(),
+            self.artist.as_deref(),
+            None,
+            self.where_expr.as_deref(),
+        )?
+        .ok_or_else(|| match &self.uri {
+            Some(uri) => From::from(format!(
+                "Track {} needs a title, artist or where, the chart files have no URIs",
+                uri
+            )),
+            None => From::from("Either one of title, artist or where need to be specified!"),
+        })
+    }
+
+    // An entry with only an artist follows all of the artist's songs
+    pub fn every_song(&self) -> bool {
+        self.artist.is_some() && self.title.is_none() && self.where_expr.is_none()
+    }
+
+    // Name of the track in reports
+    pub fn label(&self) -> String {
+        match (&self.title, &self.artist, &self.where_expr, &self.uri) {
+            (Some(title), Some(artist), _, _) => format!("{} - {}", title, artist),
+            (Some(title), None, _, _) => title.clone(),
+            (None, Some(artist), _, _) => artist.clone(),
+            (None, None, Some(expr), _) => expr.clone(),
+            (None, None, None, Some(uri)) => uri.clone(),
+            (None, None, None, None) => "-".to_string(),
+        }
+    }
+}
+
+impl Watchlist {
+    // Parse by the file extension, `.json` or `.toml`
+    pub fn parse(text: &str, extension: &str) -> Result<Self, Box<dyn Error>> {
+        let watchlist: Watchlist = match extension {
+            "json" => serde_json::from_str(text)?,
+            "toml" => toml::from_str(text)?,
+            _ => {
+                return Err(From::from(format!(
+                    "Unknown watchlist format: \"{}\", expected .toml or .json",
+                    extension
+                )))
+            }
+        };
+
+        if watchlist.tracks.is_empty() {
+            return Err(From::from("The watchlist has no tracks!"));
+        }
+        if let Some(code) = watchlist.regions.iter().find(|code| !verify_code(code)) {
+            return Err(From::from(format!("Invalid code: \"{}\"", code)));
+        }
+        // Catch broken tracks before any chart is read
+        for track in &watchlist.tracks {
+            track.filter()?;
+        }
+        Ok(watchlist)
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", e, path))?;
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        Watchlist::parse(&text, extension).map_err(|e| From::from(format!("{}: {}", path, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_toml_and_json() -> Result<(), Box<dyn Error>> {
+        let toml = r#"
+            regions = ["us", "gb"]
+
+            [[track]]
+            title = "As It Was"
+            artist = "Harry Styles"
+
+            [[track]]
+            where = 'lead:"BTS"'
+        "#;
+        let watchlist = Watchlist::parse(toml, "toml")?;
+        assert_eq!(vec!["us", "gb"], watchlist.regions);
+        assert_eq!("As It Was - Harry Styles", watchlist.tracks[0].label());
+        assert_eq!("lead:\"BTS\"", watchlist.tracks[1].label());
+
+        let json = r#"{"tracks": [{"artist": "Joji"}]}"#;
+        let watchlist = Watchlist::parse(json, "json")?;
+        assert!(watchlist.regions.is_empty());
+        assert_eq!("Joji", watchlist.tracks[0].label());
+        Ok(())
+    }
+
+    #[test]
+    fn reject_bad_watchlists() {
+        let uri_only = "[[track]]\nuri = \"spotify:track:4Dvkj6JhhA12EX05fT7y2e\"";
+        assert!(Watchlist::parse(uri_only, "toml").is_err());
+        assert!(Watchlist::parse("regions = [\"xx\"]\n[[track]]\ntitle = \"A\"", "toml").is_err());
+        assert!(Watchlist::parse("[[track]]\ntitel = \"A\"", "toml").is_err());
+        assert!(Watchlist::parse("", "yaml").is_err());
+    }
+}